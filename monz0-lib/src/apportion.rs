@@ -0,0 +1,176 @@
+//! Exact integer apportionment of an amount by weight
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The policy used to allocate the pennies left over after each share has been
+/// rounded down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Remainder {
+    /// Allocate the remainder one penny at a time to the shares with the
+    /// largest fractional parts (the 'largest remainder' method). Ties are
+    /// broken in favour of the share that appears first.
+    LargestRemainder,
+
+    /// Allocate the entire remainder to the first share
+    First,
+
+    /// Allocate the entire remainder to the last share
+    Last,
+}
+
+impl Default for Remainder {
+    fn default() -> Self {
+        Self::LargestRemainder
+    }
+}
+
+/// Split `total` into integer shares proportional to `weights`.
+///
+/// The returned shares are in the same order as the weights, and always sum to
+/// exactly `total`. The pennies left over from rounding each share down are
+/// distributed according to the [`Remainder`] policy.
+///
+/// Returns [`None`] if the weights sum to zero.
+///
+/// # Example
+///
+/// ```
+/// use monz0_lib::apportion::{apportion, Remainder};
+///
+/// let shares = apportion(100, &[1, 1, 1], Remainder::LargestRemainder).unwrap();
+/// assert_eq!(shares, vec![34, 33, 33]);
+/// ```
+#[must_use]
+pub fn apportion(total: i64, weights: &[u32], policy: Remainder) -> Option<Vec<i64>> {
+    let denominator: i128 = weights.iter().map(|&weight| i128::from(weight)).sum();
+
+    if denominator == 0 {
+        return None;
+    }
+
+    let (mut shares, remainders): (Vec<i64>, Vec<i128>) = weights
+        .iter()
+        .map(|&weight| {
+            let numerator = i128::from(total) * i128::from(weight);
+            let remainder = numerator.rem_euclid(denominator);
+
+            // a share is never larger in magnitude than the total
+            #[allow(clippy::cast_possible_truncation)]
+            let share = numerator.div_euclid(denominator) as i64;
+
+            (share, remainder)
+        })
+        .unzip();
+
+    let allocated: i64 = shares.iter().sum();
+    let leftover = total - allocated;
+
+    match policy {
+        Remainder::First => {
+            if let Some(share) = shares.first_mut() {
+                *share += leftover;
+            }
+        }
+        Remainder::Last => {
+            if let Some(share) = shares.last_mut() {
+                *share += leftover;
+            }
+        }
+        Remainder::LargestRemainder => {
+            let mut indices: Vec<usize> = (0..shares.len()).collect();
+
+            // a stable sort keeps ties in their original order
+            indices.sort_by(|&a, &b| remainders[b].cmp(&remainders[a]));
+
+            // 'leftover' is always non-negative, and less than the number of shares
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let leftover = leftover as usize;
+
+            for &index in indices.iter().take(leftover) {
+                shares[index] += 1;
+            }
+        }
+    }
+
+    Some(shares)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use test_case::test_case;
+
+    use super::{apportion, Remainder};
+
+    #[test_case(100, &[1, 1, 1], Remainder::LargestRemainder => Some(vec![34, 33, 33]); "largest remainder ties")]
+    #[test_case(100, &[1, 1, 1], Remainder::First => Some(vec![34, 33, 33]); "first")]
+    #[test_case(100, &[1, 1, 1], Remainder::Last => Some(vec![33, 33, 34]); "last")]
+    #[test_case(10, &[1, 2, 4], Remainder::LargestRemainder => Some(vec![1, 3, 6]); "largest remainder")]
+    #[test_case(10, &[1, 2, 4], Remainder::First => Some(vec![3, 2, 5]); "first uneven")]
+    #[test_case(30_000, &[2, 1], Remainder::LargestRemainder => Some(vec![20_000, 10_000]); "exact")]
+    #[test_case(100, &[0, 0], Remainder::LargestRemainder => None; "zero weight")]
+    #[test_case(100, &[], Remainder::LargestRemainder => None; "no weights")]
+    fn apportion_cases(total: i64, weights: &[u32], policy: Remainder) -> Option<Vec<i64>> {
+        apportion(total, weights, policy)
+    }
+
+    fn policy() -> impl Strategy<Value = Remainder> {
+        prop_oneof![
+            Just(Remainder::LargestRemainder),
+            Just(Remainder::First),
+            Just(Remainder::Last),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn shares_sum_to_total(
+            total in 0..i64::from(u32::MAX) * 1000,
+            weights in prop::collection::vec(0..u32::MAX, 1..20),
+            policy in policy(),
+        ) {
+            prop_assume!(weights.iter().any(|&weight| weight > 0));
+
+            let shares = apportion(total, &weights, policy).unwrap();
+
+            prop_assert_eq!(shares.len(), weights.len());
+            prop_assert_eq!(shares.iter().sum::<i64>(), total);
+        }
+
+        #[test]
+        fn shares_are_within_a_penny_of_their_quota(
+            total in 0..i64::from(u32::MAX) * 1000,
+            weights in prop::collection::vec(0..u32::MAX, 1..20),
+        ) {
+            prop_assume!(weights.iter().any(|&weight| weight > 0));
+
+            let shares = apportion(total, &weights, Remainder::LargestRemainder).unwrap();
+            let denominator: i128 = weights.iter().map(|&weight| i128::from(weight)).sum();
+
+            for (share, weight) in shares.into_iter().zip(weights) {
+                let quota = i128::from(total) * i128::from(weight);
+                let share = i128::from(share) * denominator;
+                prop_assert!(share <= quota + denominator);
+                prop_assert!(share + denominator > quota);
+            }
+        }
+
+        #[test]
+        fn zero_weights_receive_nothing(
+            total in 0..i64::from(u32::MAX),
+            weights in prop::collection::vec(0..10_u32, 1..20),
+        ) {
+            prop_assume!(weights.iter().any(|&weight| weight > 0));
+
+            let shares = apportion(total, &weights, Remainder::LargestRemainder).unwrap();
+
+            for (share, weight) in shares.into_iter().zip(weights) {
+                if weight == 0 {
+                    prop_assert_eq!(share, 0);
+                }
+            }
+        }
+    }
+}
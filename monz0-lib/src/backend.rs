@@ -0,0 +1,88 @@
+//! Backends that the [`Client`](crate::Client) can use to talk to Monzo
+//!
+//! The [`Client`](crate::Client) is generic over the [`Backend`] trait, which
+//! allows it to run against the real Monzo API, or against an
+//! [`InMemory`] backend for offline testing.
+
+use std::{fmt, sync::Arc};
+
+use async_trait::async_trait;
+use monzo::{Balance, Pot};
+
+use crate::{error::Result, Auth};
+
+mod in_memory;
+pub use in_memory::InMemory;
+
+/// The subset of the Monzo API required by the [`Client`](crate::Client)
+#[async_trait]
+pub trait Backend: std::fmt::Debug + Send + Sync {
+    /// Return the authentication information associated with the backend, if
+    /// any
+    async fn auth(&self) -> Option<Auth> {
+        None
+    }
+
+    /// Register a [`RefreshHook`], to be called whenever the backend refreshes
+    /// its authentication.
+    ///
+    /// Backends which never refresh their authentication can ignore the hook.
+    fn set_refresh_hook(&mut self, _hook: RefreshHook) {}
+
+    /// List the IDs of the accounts
+    async fn accounts(&self) -> Result<Vec<String>>;
+
+    /// Retrieve the balance for the given account
+    async fn balance(&self, account_id: &str) -> Result<Balance>;
+
+    /// Retrieve a list of [`Pot`]s associated with the given account
+    async fn pots(&self, account_id: &str) -> Result<Vec<Pot>>;
+
+    /// Deposit money from the given account into a pot
+    ///
+    /// Repeated transfers with the same `dedupe_id` must only be applied once.
+    async fn deposit_into_pot(
+        &self,
+        pot_id: &str,
+        source_account_id: &str,
+        amount: u32,
+        dedupe_id: &str,
+    ) -> Result<()>;
+
+    /// Withdraw money from a pot into the given account
+    ///
+    /// Repeated transfers with the same `dedupe_id` must only be applied once.
+    async fn withdraw_from_pot(
+        &self,
+        pot_id: &str,
+        destination_account_id: &str,
+        amount: u32,
+        dedupe_id: &str,
+    ) -> Result<()>;
+}
+
+/// A callback which is passed the new [`Auth`] whenever a [`Backend`]
+/// refreshes its authentication.
+///
+/// Refreshing the authentication may invalidate the old refresh token, so the
+/// new credentials should be persisted straight away.
+#[derive(Clone)]
+pub struct RefreshHook(Arc<dyn Fn(&Auth) + Send + Sync>);
+
+impl RefreshHook {
+    /// Create a new [`RefreshHook`] from a closure
+    pub fn new(hook: impl Fn(&Auth) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    /// Call the hook with the new [`Auth`]
+    pub fn call(&self, auth: &Auth) {
+        (self.0)(auth);
+    }
+}
+
+impl fmt::Debug for RefreshHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RefreshHook").finish()
+    }
+}
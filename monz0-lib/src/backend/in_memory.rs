@@ -0,0 +1,143 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use async_trait::async_trait;
+use monzo::{Balance, Pot};
+
+use super::Backend;
+use crate::{
+    error::{Error, Result},
+    state::copy_balance,
+    State,
+};
+
+/// An in-memory [`Backend`] for testing.
+///
+/// The backend holds a [`State`] and applies deposits and withdrawals to it,
+/// so that complete runs can be tested offline. Like the Monzo API, transfers
+/// with a dedupe ID that has already been used are ignored.
+///
+/// # Example
+///
+/// ```
+/// use monz0_lib::{backend::InMemory, Client, State};
+///
+/// let client = Client::new(InMemory::new(State::default()));
+/// ```
+#[derive(Debug, Default)]
+pub struct InMemory {
+    state: Mutex<State>,
+    dedupe_ids: Mutex<HashSet<String>>,
+}
+
+impl InMemory {
+    /// Create a new [`InMemory`] backend from an initial [`State`]
+    #[must_use]
+    pub fn new(state: State) -> Self {
+        Self {
+            state: Mutex::new(state),
+            dedupe_ids: Mutex::default(),
+        }
+    }
+
+    /// Run a closure against the current [`State`] of the backend
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the internal lock has been poisoned
+    pub fn inspect<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&State) -> R,
+    {
+        f(&self.state.lock().unwrap())
+    }
+
+    fn transfer(&self, pot_id: &str, account_id: &str, amount: i64, dedupe_id: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let mut dedupe_ids = self.dedupe_ids.lock().unwrap();
+
+        if dedupe_ids.contains(dedupe_id) {
+            tracing::debug!("ignoring duplicate transfer (dedupe ID: {})", dedupe_id);
+            return Ok(());
+        }
+
+        let account = state
+            .get_mut(account_id)
+            .ok_or_else(|| Error::NotFound(format!("account {} not found", account_id)))?;
+
+        let pot = account
+            .pots
+            .iter_mut()
+            .find(|pot| pot.id == pot_id && !pot.deleted)
+            .ok_or_else(|| Error::NotFound(format!("pot {} not found", pot_id)))?;
+
+        if amount > account.balance.balance {
+            return Err(Error::InsufficientFunds {
+                requested: amount,
+                available: account.balance.balance,
+            });
+        }
+
+        if -amount > pot.balance {
+            return Err(Error::InsufficientFunds {
+                requested: -amount,
+                available: pot.balance,
+            });
+        }
+
+        account.balance.balance -= amount;
+        pot.balance += amount;
+        dedupe_ids.insert(dedupe_id.to_string());
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Backend for InMemory {
+    async fn accounts(&self) -> Result<Vec<String>> {
+        Ok(self.state.lock().unwrap().keys().cloned().collect())
+    }
+
+    async fn balance(&self, account_id: &str) -> Result<Balance> {
+        self.state
+            .lock()
+            .unwrap()
+            .get(account_id)
+            .map(|account| copy_balance(&account.balance))
+            .ok_or_else(|| Error::NotFound(format!("account {} not found", account_id)))
+    }
+
+    async fn pots(&self, account_id: &str) -> Result<Vec<Pot>> {
+        self.state
+            .lock()
+            .unwrap()
+            .get(account_id)
+            .map(|account| account.pots.clone())
+            .ok_or_else(|| Error::NotFound(format!("account {} not found", account_id)))
+    }
+
+    async fn deposit_into_pot(
+        &self,
+        pot_id: &str,
+        source_account_id: &str,
+        amount: u32,
+        dedupe_id: &str,
+    ) -> Result<()> {
+        self.transfer(pot_id, source_account_id, i64::from(amount), dedupe_id)
+    }
+
+    async fn withdraw_from_pot(
+        &self,
+        pot_id: &str,
+        destination_account_id: &str,
+        amount: u32,
+        dedupe_id: &str,
+    ) -> Result<()> {
+        self.transfer(
+            pot_id,
+            destination_account_id,
+            -i64::from(amount),
+            dedupe_id,
+        )
+    }
+}
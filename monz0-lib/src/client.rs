@@ -1,23 +1,59 @@
-use futures_util::future::{try_join, try_join_all};
-use monzo::{inner_client::Quick, Balance, Pot};
+use std::{collections::BTreeSet, fmt};
+
+use async_trait::async_trait;
+use futures_util::future::{join_all, try_join, try_join_all};
+use monzo::{Balance, Pot};
 use serde::{Deserialize, Serialize};
 use tracing::{instrument, Level};
 
 use crate::{
-    ledger::Transactions,
+    backend::{Backend, RefreshHook},
+    error::{Error, Result},
+    ledger::{OwnedLedger, OwnedTransactions, Transfer},
+    limit::{Limiter, RateLimit},
+    report::{Direction, Entry, Report},
+    retry::RetryPolicy,
     state::{self, State},
-    Ledger,
 };
 
+mod api;
 mod auto_refresh;
+use api::{Api, Tokens};
+pub mod oauth;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct BasicAuth {
     access_token: String,
 }
 
-/// The authentication details used by the [`Client`]
-#[derive(Debug, Serialize, Deserialize)]
+/// A Monzo API client which uses a fixed access token
+struct Basic {
+    access_token: String,
+    api: Api,
+}
+
+impl fmt::Debug for Basic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Basic")
+            .field("access_token", &"<redacted>")
+            .field("api", &self.api)
+            .finish()
+    }
+}
+
+impl Basic {
+    fn new(access_token: String, url: Option<&str>) -> Self {
+        Self {
+            access_token,
+            api: Api::new(url),
+        }
+    }
+}
+
+/// The authentication details used by the [`Client`].
+///
+/// The [`Debug`] output redacts the tokens and client secret.
+#[derive(Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Auth {
     /// The credentials required for a refreshable client
@@ -30,6 +66,40 @@ pub enum Auth {
     },
 }
 
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Refreshable(auth) => f.debug_tuple("Refreshable").field(auth).finish(),
+            Self::Basic { .. } => f
+                .debug_struct("Basic")
+                .field("access_token", &"<redacted>")
+                .finish(),
+        }
+    }
+}
+
+impl Auth {
+    /// The credentials for a refreshable client, for example when they are
+    /// supplied by the environment rather than the
+    /// [OAuth login](oauth::Login)
+    #[must_use]
+    pub fn refreshable(
+        access_token: String,
+        client_id: String,
+        client_secret: String,
+        refresh_token: String,
+    ) -> Self {
+        Self::Refreshable(auto_refresh::Auth::new(
+            client_id,
+            client_secret,
+            Tokens {
+                access_token,
+                refresh_token,
+            },
+        ))
+    }
+}
+
 impl Default for Auth {
     fn default() -> Self {
         Self::Basic {
@@ -38,69 +108,113 @@ impl Default for Auth {
     }
 }
 
-#[derive(Debug)]
-enum InnerClient {
-    Quick(monzo::Client<Quick>),
-    AutoRefresh(auto_refresh::Client),
-}
-
 /// A client to the Monzo API.
 ///
 /// This client will 'auto-refresh' itself if it detects the its token has
 /// expired, provided it is instantiated with the appropriate auth
 /// ([`Auth::Refreshable`]).
+///
+/// The client can also be constructed from any other [`Backend`], such as the
+/// [`InMemory`](crate::backend::InMemory) backend used for testing.
+///
+/// Requests which fail with a temporary error are retried according to a
+/// [`RetryPolicy`], and all requests are throttled according to a
+/// [`RateLimit`].
 #[derive(Debug)]
 pub struct Client {
-    inner_client: InnerClient,
+    backend: Box<dyn Backend>,
+    retry: RetryPolicy,
+    limiter: Limiter,
 }
 
 impl From<Auth> for Client {
     fn from(auth: Auth) -> Self {
-        let inner_client = match auth {
-            Auth::Basic { access_token } => InnerClient::Quick(monzo::Client::new(access_token)),
-            Auth::Refreshable(auth) => InnerClient::AutoRefresh(auth.into()),
-        };
-        Self { inner_client }
+        match auth {
+            Auth::Basic { access_token } => Self::new(Basic::new(access_token, None)),
+            Auth::Refreshable(auth) => Self::new(auto_refresh::Client::from(auth)),
+        }
     }
 }
 
 impl Client {
-    /// Return the authentication information associated with the client
-    #[instrument(skip(self))]
-    pub async fn auth(&self) -> Auth {
-        match &self.inner_client {
-            InnerClient::Quick(client) => Auth::Basic {
-                access_token: client.access_token().to_string(),
-            },
-            InnerClient::AutoRefresh(client) => Auth::Refreshable(client.auth().await),
+    /// Create a new [`Client`] from a [`Backend`]
+    #[must_use]
+    pub fn new(backend: impl Backend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+            retry: RetryPolicy::default(),
+            limiter: Limiter::from(RateLimit::default()),
         }
     }
 
-    /// List the monzo accounts
-    #[instrument(skip(self))]
-    pub async fn accounts(&self) -> monzo::Result<Vec<monzo::Account>> {
-        match &self.inner_client {
-            InnerClient::Quick(client) => client.accounts().await,
-            InnerClient::AutoRefresh(client) => client.accounts().await,
+    /// Set the [`RetryPolicy`] used by the client
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Set the [`RateLimit`] used by the client
+    #[must_use]
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.limiter = Limiter::from(limit);
+        self
+    }
+
+    /// Create a new [`Client`] from the given [`Auth`], using a custom base URL
+    /// for the Monzo API.
+    ///
+    /// This is mostly useful for running against a fake Monzo server in tests.
+    #[must_use]
+    pub fn with_url(auth: Auth, url: impl Into<String>) -> Self {
+        let url = url.into();
+        match auth {
+            Auth::Basic { access_token } => Self::new(Basic::new(access_token, Some(&url))),
+            Auth::Refreshable(auth) => Self::new(auto_refresh::Client::new(auth, Some(&url))),
         }
     }
 
+    /// Call the given closure with the new [`Auth`] whenever the client
+    /// refreshes its access token.
+    ///
+    /// Refreshing the access token also replaces the refresh token, so the new
+    /// credentials should be saved straight away. Otherwise they would be lost
+    /// if the program then fails.
+    #[must_use]
+    pub fn with_refresh_hook(mut self, hook: impl Fn(&Auth) + Send + Sync + 'static) -> Self {
+        self.backend.set_refresh_hook(RefreshHook::new(hook));
+        self
+    }
+
+    /// Return the authentication information associated with the client, if
+    /// any
+    #[instrument(skip(self))]
+    pub async fn auth(&self) -> Option<Auth> {
+        self.backend.auth().await
+    }
+
+    /// List the IDs of the monzo accounts
+    #[instrument(skip(self))]
+    pub async fn accounts(&self) -> Result<Vec<String>> {
+        self.retry
+            .run(|| self.limiter.run(self.backend.accounts()))
+            .await
+    }
+
     /// Retrieve the balance for the given account
     #[instrument(skip(self))]
-    async fn balance(&self, account_id: &str) -> monzo::Result<Balance> {
-        match &self.inner_client {
-            InnerClient::Quick(client) => client.balance(account_id).await,
-            InnerClient::AutoRefresh(client) => client.balance(account_id).await,
-        }
+    async fn balance(&self, account_id: &str) -> Result<Balance> {
+        self.retry
+            .run(|| self.limiter.run(self.backend.balance(account_id)))
+            .await
     }
 
     /// Retrieve a list of [`Pot`]s associated with the given account
     #[instrument(skip(self))]
-    async fn pots(&self, account_id: &str) -> monzo::Result<Vec<Pot>> {
-        match &self.inner_client {
-            InnerClient::Quick(client) => client.pots(account_id).await,
-            InnerClient::AutoRefresh(client) => client.pots(account_id).await,
-        }
+    async fn pots(&self, account_id: &str) -> Result<Vec<Pot>> {
+        self.retry
+            .run(|| self.limiter.run(self.backend.pots(account_id)))
+            .await
     }
 
     #[instrument(skip(self))]
@@ -109,19 +223,18 @@ impl Client {
         pot_id: &str,
         destination_account_id: &str,
         amount: u32,
-    ) -> monzo::Result<Pot> {
-        match &self.inner_client {
-            InnerClient::Quick(client) => {
-                client
-                    .withdraw_from_pot(pot_id, destination_account_id, amount)
-                    .await
-            }
-            InnerClient::AutoRefresh(client) => {
-                client
-                    .withdraw_from_pot(pot_id, destination_account_id, amount)
-                    .await
-            }
-        }
+        dedupe_id: &str,
+    ) -> Result<()> {
+        self.retry
+            .run(|| {
+                self.limiter.run(self.backend.withdraw_from_pot(
+                    pot_id,
+                    destination_account_id,
+                    amount,
+                    dedupe_id,
+                ))
+            })
+            .await
     }
 
     #[instrument(skip(self))]
@@ -130,24 +243,23 @@ impl Client {
         pot_id: &str,
         source_account_id: &str,
         amount: u32,
-    ) -> monzo::Result<Pot> {
-        match &self.inner_client {
-            InnerClient::Quick(client) => {
-                client
-                    .deposit_into_pot(pot_id, source_account_id, amount)
-                    .await
-            }
-            InnerClient::AutoRefresh(client) => {
-                client
-                    .deposit_into_pot(pot_id, source_account_id, amount)
-                    .await
-            }
-        }
+        dedupe_id: &str,
+    ) -> Result<()> {
+        self.retry
+            .run(|| {
+                self.limiter.run(self.backend.deposit_into_pot(
+                    pot_id,
+                    source_account_id,
+                    amount,
+                    dedupe_id,
+                ))
+            })
+            .await
     }
 
     /// Retrieve the current state of the given account
     #[instrument(skip(self))]
-    async fn account_state(&self, account_id: &str) -> Result<state::Account, monzo::Error> {
+    async fn account_state(&self, account_id: &str) -> Result<state::Account> {
         let balance_fut = self.balance(account_id);
         let pots_fut = self.pots(account_id);
         let (balance, pots) = try_join(balance_fut, pots_fut).await?;
@@ -157,58 +269,460 @@ impl Client {
         Ok(state::Account { balance, pots })
     }
 
-    /// Retrieve the current state of the given account
+    /// Retrieve the current state of all the accounts
     #[instrument(skip(self))]
-    pub async fn state(&self) -> Result<State, monzo::Error> {
-        let mut state = State::default();
-        for account in self.accounts().await? {
-            let account_id = account.id;
-            let account_state = self.account_state(&account_id).await?;
-            state.insert(account_id, account_state);
-        }
+    pub async fn state(&self) -> Result<State> {
+        let account_ids = self.accounts().await?;
+        self.state_of(account_ids.iter().map(String::as_str)).await
+    }
+
+    /// Retrieve the current state of the given accounts.
+    ///
+    /// The accounts are fetched concurrently. Use
+    /// [`Operation::account_ids`](crate::Operation::account_ids) to fetch only
+    /// the accounts that an operation needs.
+    #[instrument(skip(self, account_ids))]
+    pub async fn state_of<'a>(
+        &self,
+        account_ids: impl IntoIterator<Item = &'a str>,
+    ) -> Result<State> {
+        let account_ids: BTreeSet<_> = account_ids.into_iter().collect();
+
+        let accounts = try_join_all(account_ids.into_iter().map(|account_id| async move {
+            let account_state = self.account_state(account_id).await?;
+            Ok::<_, Error>((account_id.to_string(), account_state))
+        }))
+        .await?;
 
-        Ok(state)
+        Ok(accounts.into_iter().collect())
     }
 
     /// Complete the pot withdrawals and deposits described by the given
-    /// [`Ledger`]
+    /// [`OwnedLedger`]
+    ///
+    /// A borrowed [`Ledger`](crate::Ledger) can be converted using
+    /// [`OwnedLedger::from`].
+    ///
+    /// Each transfer is sent with its dedupe ID, so processing the same
+    /// [`OwnedLedger`] again (for example, after a partial failure) won't
+    /// repeat the transfers that already succeeded.
+    ///
+    /// # Errors
+    ///
+    /// If any transfer fails, an [`Error::Incomplete`] is returned, with a
+    /// [`Report`] of which transfers were completed, which failed, and which
+    /// were skipped.
     #[instrument(skip(self))]
-    pub async fn process_ledger(&self, ledger: &Ledger<'_>) -> Result<(), monzo::Error> {
-        try_join_all(
-            ledger.into_iter().map(|(account_id, transactions)| {
+    pub async fn process_ledger(&self, ledger: &OwnedLedger) -> Result<()> {
+        let reports =
+            join_all(ledger.into_iter().map(|(account_id, transactions)| {
                 self.process_transactions(account_id, transactions)
-            }),
-        )
-        .await?;
+            }))
+            .await;
+
+        let mut report = Report::default();
+        for account_report in reports {
+            report.extend(account_report);
+        }
 
-        Ok(())
+        if report.is_complete() {
+            Ok(())
+        } else {
+            Err(Error::Incomplete(report))
+        }
     }
 
     /// Complete the pot withdrawals and deposits described by the given
-    /// account ID and [`Transactions`]
+    /// account ID and [`OwnedTransactions`]
+    ///
+    /// The deposits are skipped if any of the withdrawals fail.
     #[instrument(skip(self, transactions))]
     async fn process_transactions(
         &self,
         account_id: &str,
-        transactions: &Transactions<'_>,
-    ) -> Result<(), monzo::Error> {
-        let withdrawals = transactions.withdrawals.iter();
-        let deposits = transactions.deposits.iter();
+        transactions: &OwnedTransactions,
+    ) -> Report {
+        let entry = |direction, transfer: &Transfer| Entry {
+            account_id: account_id.to_string(),
+            direction,
+            transfer: transfer.clone(),
+        };
 
-        try_join_all(
-            withdrawals.map(|(pot, amount)| self.withdraw_from_pot(&pot.id, account_id, *amount)),
-        )
-        .await?;
+        let mut report = Report::default();
+
+        let withdrawals = join_all(transactions.withdrawals.iter().map(|transfer| async move {
+            let result = self
+                .withdraw_from_pot(
+                    &transfer.pot_id,
+                    account_id,
+                    transfer.amount,
+                    &transfer.dedupe_id,
+                )
+                .await;
+            (entry(Direction::Withdrawal, transfer), result)
+        }))
+        .await;
+        record(&mut report, withdrawals);
 
         tracing::event!(Level::DEBUG, "processed withdrawals");
 
-        try_join_all(
-            deposits.map(|(pot, amount)| self.deposit_into_pot(&pot.id, account_id, *amount)),
-        )
-        .await?;
+        if !report.failed.is_empty() {
+            tracing::event!(Level::WARN, "withdrawals failed, skipping deposits");
+            report.skipped.extend(
+                transactions
+                    .deposits
+                    .iter()
+                    .map(|transfer| entry(Direction::Deposit, transfer)),
+            );
+            return report;
+        }
+
+        let deposits = join_all(transactions.deposits.iter().map(|transfer| async move {
+            let result = self
+                .deposit_into_pot(
+                    &transfer.pot_id,
+                    account_id,
+                    transfer.amount,
+                    &transfer.dedupe_id,
+                )
+                .await;
+            (entry(Direction::Deposit, transfer), result)
+        }))
+        .await;
+        record(&mut report, deposits);
 
         tracing::event!(Level::DEBUG, "processed deposits");
 
-        Ok(())
+        report
+    }
+}
+
+/// Sort the outcomes of a batch of transfers into the [`Report`]
+fn record(report: &mut Report, outcomes: Vec<(Entry, Result<()>)>) {
+    for (entry, result) in outcomes {
+        match result {
+            Ok(()) => report.completed.push(entry),
+            Err(error) => report.failed.push((entry, error)),
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for Basic {
+    async fn auth(&self) -> Option<Auth> {
+        Some(Auth::Basic {
+            access_token: self.access_token.clone(),
+        })
+    }
+
+    async fn accounts(&self) -> Result<Vec<String>> {
+        self.api.accounts(&self.access_token).await
+    }
+
+    async fn balance(&self, account_id: &str) -> Result<Balance> {
+        self.api.balance(&self.access_token, account_id).await
+    }
+
+    async fn pots(&self, account_id: &str) -> Result<Vec<Pot>> {
+        self.api.pots(&self.access_token, account_id).await
+    }
+
+    async fn deposit_into_pot(
+        &self,
+        pot_id: &str,
+        source_account_id: &str,
+        amount: u32,
+        dedupe_id: &str,
+    ) -> Result<()> {
+        self.api
+            .deposit(
+                &self.access_token,
+                pot_id,
+                source_account_id,
+                amount,
+                dedupe_id,
+            )
+            .await
+    }
+
+    async fn withdraw_from_pot(
+        &self,
+        pot_id: &str,
+        destination_account_id: &str,
+        amount: u32,
+        dedupe_id: &str,
+    ) -> Result<()> {
+        self.api
+            .withdraw(
+                &self.access_token,
+                pot_id,
+                destination_account_id,
+                amount,
+                dedupe_id,
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use monzo::{Balance, Pot};
+
+    use super::{Auth, Client};
+    use crate::{
+        backend::{Backend, InMemory},
+        error::{Cause, Result},
+        operation::Sweep,
+        report::Direction,
+        test_support::{pot, state, ACCOUNT_ID},
+        Error, Ledger, Operation, OwnedLedger, RetryPolicy,
+    };
+
+    /// A [`Backend`] whose transfers fail with a server error the first few
+    /// times they are attempted
+    #[derive(Debug)]
+    struct Flaky {
+        inner: InMemory,
+        failures: AtomicU32,
+    }
+
+    impl Flaky {
+        fn fail(&self) -> Result<()> {
+            let remaining = self.failures.load(Ordering::SeqCst);
+            if remaining == 0 {
+                return Ok(());
+            }
+            self.failures.store(remaining - 1, Ordering::SeqCst);
+
+            Err(Cause::Api {
+                status: 503,
+                code: "service_unavailable".to_string(),
+                message: String::default(),
+                retry_after: None,
+            }
+            .into())
+        }
+    }
+
+    #[async_trait]
+    impl Backend for Flaky {
+        async fn accounts(&self) -> Result<Vec<String>> {
+            self.inner.accounts().await
+        }
+
+        async fn balance(&self, account_id: &str) -> Result<Balance> {
+            self.inner.balance(account_id).await
+        }
+
+        async fn pots(&self, account_id: &str) -> Result<Vec<Pot>> {
+            self.inner.pots(account_id).await
+        }
+
+        async fn deposit_into_pot(
+            &self,
+            pot_id: &str,
+            source_account_id: &str,
+            amount: u32,
+            dedupe_id: &str,
+        ) -> Result<()> {
+            self.fail()?;
+            self.inner
+                .deposit_into_pot(pot_id, source_account_id, amount, dedupe_id)
+                .await
+        }
+
+        async fn withdraw_from_pot(
+            &self,
+            pot_id: &str,
+            destination_account_id: &str,
+            amount: u32,
+            dedupe_id: &str,
+        ) -> Result<()> {
+            self.fail()?;
+            self.inner
+                .withdraw_from_pot(pot_id, destination_account_id, amount, dedupe_id)
+                .await
+        }
+    }
+
+    #[tokio::test]
+    async fn process_ledger_in_memory() {
+        let state = state(
+            20_000,
+            vec![
+                pot("Bills", 0, Some(5_000)),
+                pot("Savings", 0, Some(100_000)),
+            ],
+        );
+        let client = Client::new(InMemory::new(state));
+
+        let sweep = Sweep::new(ACCOUNT_ID.to_string(), 100)
+            .with_pot("bills".to_string())
+            .with_pot("savings".to_string());
+
+        let state = client.state().await.unwrap();
+        let ledger = sweep.transactions(&state).unwrap();
+        client.process_ledger(&ledger.into()).await.unwrap();
+
+        let state = client.state().await.unwrap();
+        let account = &state[ACCOUNT_ID];
+        assert_eq!(account.balance.balance, 10_000);
+
+        let balances: Vec<_> = account.pots.iter().map(|pot| pot.balance).collect();
+        assert_eq!(balances, vec![5_000, 5_000]);
+    }
+
+    #[tokio::test]
+    async fn failed_deposit() {
+        let savings = pot("Savings", 1_000, None);
+        let bills = pot("Bills", 0, None);
+        let missing = pot("Holiday", 0, None);
+
+        let client = Client::new(InMemory::new(state(
+            0,
+            vec![savings.clone(), bills.clone()],
+        )));
+
+        let mut ledger = Ledger::default();
+        ledger.push(ACCOUNT_ID, &savings, -1_000).unwrap();
+        ledger.push(ACCOUNT_ID, &bills, 500).unwrap();
+        ledger.push(ACCOUNT_ID, &missing, 500).unwrap();
+
+        let report = match client.process_ledger(&OwnedLedger::from(ledger)).await {
+            Err(Error::Incomplete(report)) => report,
+            other => panic!("expected an incomplete report, got {:?}", other),
+        };
+
+        let completed: Vec<_> = report
+            .completed
+            .iter()
+            .map(|entry| (entry.direction, entry.transfer.pot_name.as_str()))
+            .collect();
+        assert_eq!(
+            completed,
+            vec![
+                (Direction::Withdrawal, "Savings"),
+                (Direction::Deposit, "Bills")
+            ]
+        );
+
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0.transfer.pot_name, "Holiday");
+        assert!(report.skipped.is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_withdrawal_skips_deposits() {
+        let savings = pot("Savings", 1_000, None);
+        let bills = pot("Bills", 0, None);
+
+        let client = Client::new(InMemory::new(state(
+            0,
+            vec![savings.clone(), bills.clone()],
+        )));
+
+        let mut ledger = Ledger::default();
+        ledger.push(ACCOUNT_ID, &savings, -2_000).unwrap();
+        ledger.push(ACCOUNT_ID, &bills, 2_000).unwrap();
+
+        let report = match client.process_ledger(&OwnedLedger::from(ledger)).await {
+            Err(Error::Incomplete(report)) => report,
+            other => panic!("expected an incomplete report, got {:?}", other),
+        };
+
+        assert!(report.completed.is_empty());
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].direction, Direction::Deposit);
+    }
+
+    #[tokio::test]
+    async fn repeated_ledger_is_deduplicated() {
+        let savings = pot("Savings", 0, None);
+        let client = Client::new(InMemory::new(state(1_000, vec![savings.clone()])));
+
+        let mut ledger = Ledger::default();
+        ledger.push(ACCOUNT_ID, &savings, 500).unwrap();
+        let ledger = OwnedLedger::from(ledger);
+
+        client.process_ledger(&ledger).await.unwrap();
+        client.process_ledger(&ledger).await.unwrap();
+
+        let state = client.state().await.unwrap();
+        assert_eq!(state[ACCOUNT_ID].balance.balance, 500);
+        assert_eq!(state[ACCOUNT_ID].pots[0].balance, 500);
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried() {
+        let savings = pot("Savings", 0, None);
+        let backend = Flaky {
+            inner: InMemory::new(state(1_000, vec![savings.clone()])),
+            failures: AtomicU32::new(2),
+        };
+        let client = Client::new(backend).with_retry_policy(RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::ZERO,
+            ..RetryPolicy::default()
+        });
+
+        let mut ledger = Ledger::default();
+        ledger.push(ACCOUNT_ID, &savings, 500).unwrap();
+
+        client
+            .process_ledger(&OwnedLedger::from(ledger))
+            .await
+            .unwrap();
+
+        let state = client.state().await.unwrap();
+        assert_eq!(state[ACCOUNT_ID].pots[0].balance, 500);
+    }
+
+    #[tokio::test]
+    async fn state_of() {
+        let mut initial = state(1_000, vec![pot("Savings", 0, None)]);
+        let other = state(2_000, vec![]).remove(ACCOUNT_ID).unwrap();
+        initial.insert("acc_other".to_string(), other);
+
+        let client = Client::new(InMemory::new(initial));
+
+        assert_eq!(client.state().await.unwrap().len(), 2);
+
+        let state = client.state_of([ACCOUNT_ID, ACCOUNT_ID]).await.unwrap();
+        assert_eq!(state.len(), 1);
+        assert_eq!(state[ACCOUNT_ID].balance.balance, 1_000);
+
+        assert!(matches!(
+            client.state_of(["acc_missing"]).await,
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn debug_redacts_secrets() {
+        let refreshable: Auth = serde_yaml::from_str(
+            "
+            access_token: ACCESS_TOKEN
+            client_id: CLIENT_ID
+            client_secret: CLIENT_SECRET
+            refresh_token: REFRESH_TOKEN
+            ",
+        )
+        .unwrap();
+        let basic = Auth::default();
+
+        for auth in [refreshable, basic] {
+            let client = format!("{:?}", Client::from(auth));
+
+            for secret in ["ACCESS_TOKEN", "CLIENT_SECRET", "REFRESH_TOKEN"] {
+                assert!(!client.contains(secret), "{}", client);
+            }
+        }
     }
 }
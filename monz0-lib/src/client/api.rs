@@ -0,0 +1,223 @@
+//! A minimal client for the subset of the Monzo API used by `monz0`.
+//!
+//! `monzo-lib` hardcodes the address of the Monzo API, and generates a fresh
+//! dedupe ID for every pot transfer. This client talks to the API directly, so
+//! that the base URL can be overridden (to run against a fake server) and so
+//! that transfers can be retried without being applied twice. The response
+//! types from `monzo-lib` are reused.
+
+use std::time::Duration;
+
+use monzo::{Balance, Pot};
+use reqwest::{header::RETRY_AFTER, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::error::{Cause, Error, Result};
+
+/// The base URL of the Monzo API
+const DEFAULT_URL: &str = "https://api.monzo.com";
+
+#[derive(Debug)]
+pub struct Api {
+    http: reqwest::Client,
+    url: String,
+}
+
+/// The access and refresh tokens returned when refreshing authentication
+#[derive(Debug, Deserialize)]
+pub struct Tokens {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+/// The body of an error response from the Monzo API
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ApiError {
+    code: String,
+    message: String,
+}
+
+impl Api {
+    /// Create a new [`Api`] client, optionally overriding the base URL of the
+    /// Monzo API
+    pub fn new(url: Option<&str>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.unwrap_or(DEFAULT_URL).trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// List the IDs of the accounts
+    pub async fn accounts(&self, access_token: &str) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Account {
+            id: String,
+        }
+
+        #[derive(Deserialize)]
+        struct Response {
+            accounts: Vec<Account>,
+        }
+
+        let response: Response = self
+            .send(
+                self.http
+                    .get(self.url("accounts"))
+                    .bearer_auth(access_token),
+            )
+            .await?;
+
+        Ok(response
+            .accounts
+            .into_iter()
+            .map(|account| account.id)
+            .collect())
+    }
+
+    pub async fn balance(&self, access_token: &str, account_id: &str) -> Result<Balance> {
+        self.send(
+            self.http
+                .get(self.url("balance"))
+                .bearer_auth(access_token)
+                .query(&[("account_id", account_id)]),
+        )
+        .await
+    }
+
+    pub async fn pots(&self, access_token: &str, account_id: &str) -> Result<Vec<Pot>> {
+        #[derive(Deserialize)]
+        struct Response {
+            pots: Vec<Pot>,
+        }
+
+        let response: Response = self
+            .send(
+                self.http
+                    .get(self.url("pots"))
+                    .bearer_auth(access_token)
+                    .query(&[("current_account_id", account_id)]),
+            )
+            .await?;
+
+        Ok(response.pots)
+    }
+
+    pub async fn deposit(
+        &self,
+        access_token: &str,
+        pot_id: &str,
+        source_account_id: &str,
+        amount: u32,
+        dedupe_id: &str,
+    ) -> Result<()> {
+        self.transfer(
+            access_token,
+            &format!("pots/{}/deposit", pot_id),
+            &[
+                ("source_account_id", source_account_id),
+                ("amount", &amount.to_string()),
+                ("dedupe_id", dedupe_id),
+            ],
+        )
+        .await
+    }
+
+    pub async fn withdraw(
+        &self,
+        access_token: &str,
+        pot_id: &str,
+        destination_account_id: &str,
+        amount: u32,
+        dedupe_id: &str,
+    ) -> Result<()> {
+        self.transfer(
+            access_token,
+            &format!("pots/{}/withdraw", pot_id),
+            &[
+                ("destination_account_id", destination_account_id),
+                ("amount", &amount.to_string()),
+                ("dedupe_id", dedupe_id),
+            ],
+        )
+        .await
+    }
+
+    /// Exchange a refresh token for a new pair of access and refresh tokens
+    pub async fn refresh(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        refresh_token: &str,
+    ) -> Result<Tokens> {
+        self.send(self.http.post(self.url("oauth2/token")).form(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("refresh_token", refresh_token),
+        ]))
+        .await
+    }
+
+    /// Exchange an authorisation code, from the OAuth redirect, for a pair of
+    /// access and refresh tokens
+    pub async fn exchange_code(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        redirect_uri: &str,
+        code: &str,
+    ) -> Result<Tokens> {
+        self.send(self.http.post(self.url("oauth2/token")).form(&[
+            ("grant_type", "authorization_code"),
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("redirect_uri", redirect_uri),
+            ("code", code),
+        ]))
+        .await
+    }
+
+    async fn transfer(&self, access_token: &str, path: &str, form: &[(&str, &str)]) -> Result<()> {
+        let _pot: serde::de::IgnoredAny = self
+            .send(
+                self.http
+                    .put(self.url(path))
+                    .bearer_auth(access_token)
+                    .form(form),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.url, path)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let response = request.send().await?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response.json().await?);
+        }
+
+        // only the 'delay-seconds' form of the header is supported
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+
+        let body: ApiError = response.json().await.unwrap_or_default();
+
+        Err(Error::from(Cause::Api {
+            status: status.as_u16(),
+            code: body.code,
+            message: body.message,
+            retry_after,
+        }))
+    }
+}
@@ -1,10 +1,17 @@
-use std::future::Future;
+use std::{fmt, future::Future};
 
-use monzo::{inner_client::Refreshable, Account, Balance, Pot};
+use async_trait::async_trait;
+use monzo::{Balance, Pot};
 use serde::{Deserialize, Serialize};
 use tokio::sync::{Mutex, RwLock};
 
-#[derive(Debug, Serialize, Deserialize)]
+use super::{api::Tokens, Api};
+use crate::{
+    backend::{Backend, RefreshHook},
+    error::{Cause, Error, Result},
+};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Auth {
     access_token: String,
     client_id: String,
@@ -12,115 +19,283 @@ pub struct Auth {
     refresh_token: String,
 }
 
+impl Auth {
+    pub(super) fn new(client_id: String, client_secret: String, tokens: Tokens) -> Self {
+        Self {
+            access_token: tokens.access_token,
+            client_id,
+            client_secret,
+            refresh_token: tokens.refresh_token,
+        }
+    }
+}
+
+/// The tokens and client secret are redacted, so they don't end up in logs
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Auth")
+            .field("access_token", &"<redacted>")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"<redacted>")
+            .field("refresh_token", &"<redacted>")
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 pub struct Client {
-    client: RwLock<monzo::Client<Refreshable>>,
+    auth: RwLock<Auth>,
+    api: Api,
     refresh_lock: Mutex<()>,
+    refresh_hook: Option<RefreshHook>,
 }
 
-impl Client {
-    pub async fn auth(&self) -> Auth {
-        let client = self.client.read().await;
+#[async_trait]
+impl Backend for Client {
+    async fn auth(&self) -> Option<super::Auth> {
+        Some(super::Auth::Refreshable(self.auth.read().await.clone()))
+    }
 
-        Auth {
-            access_token: client.access_token().to_string(),
-            client_id: client.client_id().to_string(),
-            client_secret: client.client_secret().to_string(),
-            refresh_token: client.refresh_token().to_string(),
-        }
+    fn set_refresh_hook(&mut self, hook: RefreshHook) {
+        self.refresh_hook = Some(hook);
     }
 
-    pub async fn accounts(&self) -> monzo::Result<Vec<Account>> {
-        self.with_retry(|| async { self.client.read().await.accounts().await })
+    async fn accounts(&self) -> Result<Vec<String>> {
+        self.with_retry(|access_token| async move { self.api.accounts(&access_token).await })
             .await
     }
 
-    pub async fn balance(&self, account_id: &str) -> monzo::Result<Balance> {
-        self.with_retry(|| async { self.client.read().await.balance(account_id).await })
-            .await
+    async fn balance(&self, account_id: &str) -> Result<Balance> {
+        self.with_retry(
+            |access_token| async move { self.api.balance(&access_token, account_id).await },
+        )
+        .await
     }
 
-    pub async fn pots(&self, account_id: &str) -> monzo::Result<Vec<Pot>> {
-        self.with_retry(|| async { self.client.read().await.pots(account_id).await })
-            .await
+    async fn pots(&self, account_id: &str) -> Result<Vec<Pot>> {
+        self.with_retry(
+            |access_token| async move { self.api.pots(&access_token, account_id).await },
+        )
+        .await
     }
 
-    pub async fn withdraw_from_pot(
+    async fn withdraw_from_pot(
         &self,
         pot_id: &str,
         destination_account_id: &str,
         amount: u32,
-    ) -> monzo::Result<Pot> {
-        self.with_retry(|| async {
-            self.client
-                .read()
-                .await
-                .withdraw_from_pot(pot_id, destination_account_id, amount)
+        dedupe_id: &str,
+    ) -> Result<()> {
+        self.with_retry(|access_token| async move {
+            self.api
+                .withdraw(
+                    &access_token,
+                    pot_id,
+                    destination_account_id,
+                    amount,
+                    dedupe_id,
+                )
                 .await
         })
         .await
     }
 
-    pub async fn deposit_into_pot(
+    async fn deposit_into_pot(
         &self,
         pot_id: &str,
         source_account_id: &str,
         amount: u32,
-    ) -> monzo::Result<Pot> {
-        self.with_retry(|| async {
-            self.client
-                .read()
-                .await
-                .deposit_into_pot(pot_id, source_account_id, amount)
+        dedupe_id: &str,
+    ) -> Result<()> {
+        self.with_retry(|access_token| async move {
+            self.api
+                .deposit(&access_token, pot_id, source_account_id, amount, dedupe_id)
                 .await
         })
         .await
     }
+}
+
+impl Client {
+    /// Create a new [`Client`], optionally overriding the base URL of the
+    /// Monzo API
+    pub fn new(auth: Auth, url: Option<&str>) -> Self {
+        Self {
+            auth: RwLock::new(auth),
+            api: Api::new(url),
+            refresh_lock: Mutex::new(()),
+            refresh_hook: None,
+        }
+    }
+
+    async fn access_token(&self) -> String {
+        self.auth.read().await.access_token.clone()
+    }
 
-    async fn with_retry<F, Fut, R>(&self, f: F) -> monzo::Result<R>
+    /// Retry a request once, after refreshing the access token, if it fails
+    /// because the access token has expired. A request which was rejected
+    /// because a concurrent request refreshed the access token while it was in
+    /// flight is also retried, with the new access token.
+    ///
+    /// The request is passed the current access token. Transfers are retried
+    /// with the same dedupe ID, so a transfer that succeeded despite the error
+    /// won't be repeated.
+    async fn with_retry<F, Fut, R>(&self, f: F) -> Result<R>
     where
-        F: Fn() -> Fut,
-        Fut: Future<Output = monzo::Result<R>>,
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<R>>,
     {
-        let response = f().await;
-
-        if response.is_err() {
-            tracing::warn!("authentication failed, access token may have expired");
-            self.refresh_auth().await?;
-            return f().await;
+        let access_token = self.access_token().await;
+        match f(access_token.clone()).await {
+            Err(Error::AuthExpired(_)) => {
+                tracing::warn!("authentication failed, access token may have expired");
+                self.refresh_auth(&access_token).await?;
+                f(self.access_token().await).await
+            }
+            Err(Error::Permanent(Cause::Api { status: 401, .. }))
+                if self.is_replaced(&access_token).await =>
+            {
+                tracing::debug!("access token was refreshed while the request was in flight");
+                f(self.access_token().await).await
+            }
+            response => response,
         }
+    }
 
-        response
+    /// Whether the given access token has been replaced, waiting for any
+    /// refresh in progress to finish
+    async fn is_replaced(&self, access_token: &str) -> bool {
+        let _refresh_lock = self.refresh_lock.lock().await;
+        self.access_token().await != access_token
     }
 
-    async fn refresh_auth(&self) -> monzo::Result<()> {
-        tracing::info!("attempting access token refresh");
+    /// Refresh the access token, unless the given (expired) access token has
+    /// already been replaced by a concurrent request
+    async fn refresh_auth(&self, expired_token: &str) -> Result<()> {
+        let _refresh_lock = self.refresh_lock.lock().await;
 
-        let _refresh_lock = if let Ok(lock) = self.refresh_lock.try_lock() {
-            lock
-        } else {
-            tracing::debug!("another thread is already refreshing auth");
+        if self.access_token().await != expired_token {
+            tracing::debug!("access token has already been refreshed");
             return Ok(());
+        }
+
+        tracing::info!("attempting access token refresh");
+
+        let tokens = {
+            let auth = self.auth.read().await;
+            self.api
+                .refresh(&auth.client_id, &auth.client_secret, &auth.refresh_token)
+                .await?
         };
 
-        self.client.write().await.refresh_auth().await?;
+        let auth = {
+            let mut auth = self.auth.write().await;
+            auth.access_token = tokens.access_token;
+            auth.refresh_token = tokens.refresh_token;
+            auth.clone()
+        };
         tracing::info!("access token refreshed");
 
+        if let Some(hook) = &self.refresh_hook {
+            hook.call(&super::Auth::Refreshable(auth));
+        }
+
         Ok(())
     }
 }
 
 impl From<Auth> for Client {
     fn from(auth: Auth) -> Self {
-        let client = monzo::Client::new(auth.access_token).with_refresh_tokens(
-            auth.client_id,
-            auth.client_secret,
-            auth.refresh_token,
-        );
+        Self::new(auth, None)
+    }
+}
 
-        Self {
-            client: RwLock::new(client),
-            refresh_lock: Mutex::new(()),
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use monz0_test_server::{Fixture, Server};
+    use test_case::test_case;
+
+    use super::{Auth, Client};
+    use crate::{
+        backend::Backend,
+        error::{Cause, Error},
+    };
+
+    fn api_error(status: u16) -> Error {
+        Cause::Api {
+            status,
+            code: String::default(),
+            message: String::default(),
+            retry_after: None,
+        }
+        .into()
+    }
+
+    fn auth(access_token: &str) -> Auth {
+        Auth {
+            access_token: access_token.to_string(),
+            client_id: "CLIENT_ID".to_string(),
+            client_secret: "CLIENT_SECRET".to_string(),
+            refresh_token: "REFRESH_TOKEN".to_string(),
         }
     }
+
+    fn fixture() -> Fixture {
+        Fixture::from_yaml(include_str!(
+            "../../../monz0-test-server/fixtures/basic.yml"
+        ))
+        .unwrap()
+    }
+
+    #[test_case(400 => matches Error::Permanent(_); "permanent")]
+    #[test_case(429 => matches Error::RateLimited(_); "rate limited")]
+    #[test_case(503 => matches Error::Transient(_); "transient")]
+    #[tokio::test]
+    async fn other_errors_are_not_retried(status: u16) -> Error {
+        // nothing is listening here, so an attempted refresh would fail with a
+        // connection error
+        let client = Client::new(auth("ACCESS_TOKEN"), Some("http://127.0.0.1:1"));
+        let attempts = AtomicUsize::default();
+
+        let error = client
+            .with_retry(|_| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(api_error(status))
+            })
+            .await
+            .unwrap_err();
+
+        assert_eq!(attempts.into_inner(), 1);
+        error
+    }
+
+    #[tokio::test]
+    async fn refreshes_expired_auth() {
+        let mut fixture = fixture();
+        fixture.auth.expired = true;
+        let server = Server::start(fixture);
+
+        let client = Client::new(auth("ACCESS_TOKEN"), Some(&server.url()));
+
+        assert_eq!(client.accounts().await.unwrap(), vec!["acc_1234"]);
+        assert_eq!(server.refreshes(), 1);
+        assert_eq!(client.access_token().await, "ACCESS_TOKEN_1");
+    }
+
+    #[test_case("WRONG_ACCESS_TOKEN", false => matches Error::Permanent(Cause::Api { status: 401, .. }); "invalid access token")]
+    #[test_case("ACCESS_TOKEN", true => matches Error::Permanent(Cause::Api { status: 403, .. }); "awaiting approval")]
+    #[tokio::test]
+    async fn only_refreshes_expired_auth(access_token: &str, awaiting_approval: bool) -> Error {
+        let mut fixture = fixture();
+        fixture.auth.awaiting_approval = awaiting_approval;
+        let server = Server::start(fixture);
+
+        let client = Client::new(auth(access_token), Some(&server.url()));
+        let error = client.accounts().await.unwrap_err();
+
+        assert_eq!(server.refreshes(), 0);
+        error
+    }
 }
@@ -0,0 +1,161 @@
+//! Logging in to Monzo with the OAuth authorisation code flow
+//!
+//! See [`Login`].
+
+use reqwest::Url;
+
+use super::{auto_refresh, Api, Auth};
+use crate::error::Result;
+
+/// The address of the Monzo authorisation page
+const AUTH_URL: &str = "https://auth.monzo.com/";
+
+/// An in-progress login, using the OAuth authorisation code flow.
+///
+/// 1. The user visits the [authorisation URL](Login::authorisation_url) and
+///    logs in to Monzo.
+/// 2. Monzo redirects the user to the `redirect_uri`, with an authorisation
+///    code and the [state token](Login::state) in the query string.
+/// 3. The code is [exchanged](Login::complete) for refreshable [`Auth`]
+///    credentials.
+///
+/// Monzo then asks the user to approve access in the Monzo app. Until they do,
+/// the Monzo API responds to requests with '403 Forbidden'.
+///
+/// The `redirect_uri` must match one registered for the OAuth client in the
+/// Monzo developer portal.
+///
+/// # Example
+///
+/// ```no_run
+/// use monz0_lib::oauth::Login;
+///
+/// # async fn example() -> Result<(), monz0_lib::Error> {
+/// let login = Login::new(
+///     "CLIENT_ID".to_string(),
+///     "CLIENT_SECRET".to_string(),
+///     "http://127.0.0.1:8080/callback".to_string(),
+///     None,
+/// );
+///
+/// println!("log in at {}", login.authorisation_url());
+///
+/// // ... receive the code from the redirect, and check the state token ...
+/// # let code = "";
+///
+/// let auth = login.complete(code).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Login {
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    state: String,
+    api: Api,
+}
+
+impl Login {
+    /// Start a new login, optionally overriding the base URL of the Monzo API
+    #[must_use]
+    pub fn new(
+        client_id: String,
+        client_secret: String,
+        redirect_uri: String,
+        api_url: Option<&str>,
+    ) -> Self {
+        Self {
+            client_id,
+            client_secret,
+            redirect_uri,
+            state: format!("{:032x}", rand::random::<u128>()),
+            api: Api::new(api_url),
+        }
+    }
+
+    /// The URL of the Monzo page where the user logs in
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)]
+    pub fn authorisation_url(&self) -> String {
+        Url::parse_with_params(
+            AUTH_URL,
+            &[
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", &self.redirect_uri),
+                ("response_type", "code"),
+                ("state", &self.state),
+            ],
+        )
+        .expect("the authorisation URL is valid")
+        .to_string()
+    }
+
+    /// The random state token included in the authorisation URL.
+    ///
+    /// The redirect must include the same token, otherwise it didn't come from
+    /// this login and should be rejected.
+    #[must_use]
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    /// Exchange the authorisation code from the redirect for refreshable
+    /// [`Auth`] credentials
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the code can't be exchanged, for example because it
+    /// has expired or has already been used.
+    pub async fn complete(self, code: &str) -> Result<Auth> {
+        let tokens = self
+            .api
+            .exchange_code(
+                &self.client_id,
+                &self.client_secret,
+                &self.redirect_uri,
+                code,
+            )
+            .await?;
+
+        Ok(Auth::Refreshable(auto_refresh::Auth::new(
+            self.client_id,
+            self.client_secret,
+            tokens,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use super::Login;
+
+    #[test]
+    fn authorisation_url() {
+        let login = Login::new(
+            "CLIENT_ID".to_string(),
+            "CLIENT_SECRET".to_string(),
+            "http://127.0.0.1:8080/callback".to_string(),
+            None,
+        );
+
+        let url = Url::parse(&login.authorisation_url()).unwrap();
+        assert_eq!(url.host_str(), Some("auth.monzo.com"));
+
+        let params: Vec<_> = url.query_pairs().into_owned().collect();
+        assert_eq!(
+            params,
+            vec![
+                ("client_id".to_string(), "CLIENT_ID".to_string()),
+                (
+                    "redirect_uri".to_string(),
+                    "http://127.0.0.1:8080/callback".to_string()
+                ),
+                ("response_type".to_string(), "code".to_string()),
+                ("state".to_string(), login.state().to_string()),
+            ]
+        );
+    }
+}
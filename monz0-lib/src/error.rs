@@ -0,0 +1,214 @@
+//! Error types returned by the [`Client`](crate::Client) and its
+//! [`Backend`](crate::backend::Backend)s
+
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+/// Errors that can occur when communicating with a
+/// [`Backend`](crate::backend::Backend)
+///
+/// Errors from the Monzo API are classified by whether (and how) they can be
+/// recovered from. See [`Cause`] for the underlying error.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The access token has expired. Refreshing the access token may resolve
+    /// the error.
+    #[error("authentication failed: {0}")]
+    AuthExpired(#[source] Cause),
+
+    /// A temporary failure, such as a network error or a server error. The
+    /// request may succeed if it is retried.
+    #[error("temporary failure: {0}")]
+    Transient(#[source] Cause),
+
+    /// Too many requests have been made to the Monzo API. The request may
+    /// succeed if it is retried later.
+    #[error("rate limited: {0}")]
+    RateLimited(#[source] Cause),
+
+    /// An error which won't be resolved by retrying the request
+    #[error("{0}")]
+    Permanent(#[source] Cause),
+
+    /// The requested account or pot does not exist
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    /// There are insufficient funds to complete a transfer
+    #[error("insufficient funds: requested {requested}, available {available}")]
+    InsufficientFunds {
+        /// The amount requested
+        requested: i64,
+
+        /// The amount available
+        available: i64,
+    },
+
+    /// Some of the transfers in a ledger failed or were skipped. The
+    /// [`Report`](crate::report::Report) records exactly which transfers were
+    /// completed.
+    #[error("the ledger was only partially processed:\n{0}")]
+    Incomplete(crate::report::Report),
+}
+
+/// The underlying cause of an error communicating with the Monzo API
+#[derive(Debug, thiserror::Error)]
+pub enum Cause {
+    /// An error returned by the Monzo API client
+    #[error(transparent)]
+    Monzo(monzo::Error),
+
+    /// An error making a request to the Monzo API
+    #[error(transparent)]
+    Http(reqwest::Error),
+
+    /// The Monzo API responded with an error
+    #[error("the Monzo API returned an error ({status}): {message}")]
+    Api {
+        /// The HTTP status code
+        status: u16,
+
+        /// The Monzo error code
+        code: String,
+
+        /// A description of the error
+        message: String,
+
+        /// How long the Monzo API asked the client to wait before retrying,
+        /// from the `Retry-After` header
+        retry_after: Option<Duration>,
+    },
+}
+
+impl Error {
+    /// Whether the request which caused the error may succeed if it is
+    /// retried, without any intervention
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Transient(_) | Self::RateLimited(_))
+    }
+
+    /// How long the Monzo API asked the client to wait before retrying the
+    /// request, if at all
+    #[must_use]
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::AuthExpired(cause)
+            | Self::Transient(cause)
+            | Self::RateLimited(cause)
+            | Self::Permanent(cause) => cause.retry_after(),
+            _ => None,
+        }
+    }
+}
+
+impl Cause {
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Api { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl From<Cause> for Error {
+    fn from(cause: Cause) -> Self {
+        let classify = match &cause {
+            Cause::Monzo(error) => classify_monzo(error),
+            Cause::Http(error) => classify_http(error),
+            Cause::Api { status, code, .. } => match StatusCode::from_u16(*status) {
+                // refreshing only helps if the access token has expired, rather
+                // than being invalid or revoked
+                Ok(StatusCode::UNAUTHORIZED) if !code.ends_with(".expired") => Self::Permanent,
+                Ok(status) => classify_status(status),
+                Err(_) => Self::Permanent,
+            },
+        };
+
+        classify(cause)
+    }
+}
+
+impl From<monzo::Error> for Error {
+    fn from(error: monzo::Error) -> Self {
+        Cause::Monzo(error).into()
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        Cause::Http(error).into()
+    }
+}
+
+/// The [`Error`] variant that a [`Cause`] is wrapped in
+type Classify = fn(Cause) -> Error;
+
+fn classify_status(status: StatusCode) -> Classify {
+    match status {
+        StatusCode::UNAUTHORIZED => Error::AuthExpired,
+        StatusCode::TOO_MANY_REQUESTS => Error::RateLimited,
+        StatusCode::REQUEST_TIMEOUT => Error::Transient,
+        status if status.is_server_error() => Error::Transient,
+        _ => Error::Permanent,
+    }
+}
+
+fn classify_http(error: &reqwest::Error) -> Classify {
+    if let Some(status) = error.status() {
+        classify_status(status)
+    } else if error.is_timeout() || error.is_connect() || error.is_request() {
+        Error::Transient
+    } else {
+        Error::Permanent
+    }
+}
+
+fn classify_monzo(error: &monzo::Error) -> Classify {
+    match error {
+        monzo::Error::AuthExpired => Error::AuthExpired,
+        monzo::Error::Client(status) | monzo::Error::Server(status) => classify_status(*status),
+        monzo::Error::Http(error) => classify_http(error),
+        monzo::Error::Serde(_) => Error::Permanent,
+    }
+}
+
+/// Convenient alias for a [`Result`](std::result::Result) with a crate
+/// [`Error`]
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+    use test_case::test_case;
+
+    use super::{Cause, Error};
+
+    #[test_case(401, "unauthorized.bad_access_token.expired" => matches Error::AuthExpired(_); "expired access token")]
+    #[test_case(401, "unauthorized.bad_access_token" => matches Error::Permanent(_); "invalid access token")]
+    #[test_case(403, "forbidden.insufficient_permissions" => matches Error::Permanent(_); "forbidden")]
+    #[test_case(429, "" => matches Error::RateLimited(_); "too many requests")]
+    #[test_case(408, "" => matches Error::Transient(_); "request timeout")]
+    #[test_case(500, "" => matches Error::Transient(_); "internal server error")]
+    #[test_case(504, "" => matches Error::Transient(_); "gateway timeout")]
+    #[test_case(400, "" => matches Error::Permanent(_); "bad request")]
+    #[test_case(404, "" => matches Error::Permanent(_); "not found")]
+    fn classify_api_error(status: u16, code: &str) -> Error {
+        Cause::Api {
+            status,
+            code: code.to_string(),
+            message: String::default(),
+            retry_after: None,
+        }
+        .into()
+    }
+
+    #[test_case(monzo::Error::AuthExpired => matches Error::AuthExpired(_); "auth expired")]
+    #[test_case(monzo::Error::Server(StatusCode::BAD_GATEWAY) => matches Error::Transient(_); "server error")]
+    #[test_case(monzo::Error::Client(StatusCode::TOO_MANY_REQUESTS) => matches Error::RateLimited(_); "rate limited")]
+    #[test_case(monzo::Error::Client(StatusCode::FORBIDDEN) => matches Error::Permanent(_); "client error")]
+    fn classify_monzo_error(error: monzo::Error) -> Error {
+        error.into()
+    }
+}
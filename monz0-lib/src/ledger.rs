@@ -1,8 +1,59 @@
+//! Ledgers of the transactions generated by an [`Operation`](crate::Operation)
+
 use std::collections::HashMap;
 
 mod transactions;
 use monzo::Pot;
 pub use transactions::Transactions;
+mod owned;
+pub use owned::{OwnedLedger, OwnedTransactions, Transfer};
+
+/// Errors that can occur when building or validating a ledger
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum Error {
+    /// Transfers are limited to the range of a [`u32`] (in minor units).
+    /// Amounts outside of this range are rejected rather than truncated.
+    #[error("the amount {amount} for pot '{pot}' is out of range")]
+    AmountOutOfRange {
+        /// The name of the pot
+        pot: String,
+
+        /// The requested amount
+        amount: i64,
+    },
+
+    /// An account or pot referenced by the ledger doesn't exist
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    /// The withdrawals from a pot exceed its balance
+    #[error("pot '{pot}' would be overdrawn: balance {balance}, withdrawals {withdrawals}")]
+    Overdrawn {
+        /// The name of the pot
+        pot: String,
+
+        /// The balance of the pot
+        balance: i64,
+
+        /// The total amount withdrawn from the pot
+        withdrawals: i64,
+    },
+
+    /// The deposits from an account exceed the available cash
+    #[error(
+        "insufficient funds in account {account_id}: available {available}, deposits {deposits}"
+    )]
+    InsufficientFunds {
+        /// The ID of the account
+        account_id: String,
+
+        /// The cash available, including any withdrawals from pots
+        available: i64,
+
+        /// The total amount deposited into pots
+        deposits: i64,
+    },
+}
 
 /// Represents a ledger of transactions (deposits and withdrawals) associated
 /// with their respective accounts
@@ -13,11 +64,16 @@ pub struct Ledger<'a> {
 
 impl<'a> Ledger<'a> {
     /// Add a new transaction to the [`Ledger`]
-    pub fn push(&mut self, account_id: &'a str, pot: &'a Pot, amount: i64) {
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the amount is out of range. See
+    /// [`Transactions::push`].
+    pub fn push(&mut self, account_id: &'a str, pot: &'a Pot, amount: i64) -> Result<(), Error> {
         self.transactions
             .entry(account_id)
             .or_default()
-            .push(pot, amount);
+            .push(pot, amount)
     }
 
     /// Checks whether there are zero transactions in the ledger
@@ -35,6 +91,13 @@ impl<'a> Ledger<'a> {
 
         true
     }
+
+    /// Iterate over the transactions of each account in the [`Ledger`]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Transactions<'a>)> {
+        self.transactions
+            .iter()
+            .map(|(account_id, transactions)| (*account_id, transactions))
+    }
 }
 
 impl<'a> IntoIterator for Ledger<'a> {
@@ -53,8 +116,6 @@ impl<'a> IntoIterator for &'a Ledger<'a> {
     type IntoIter = impl Iterator<Item = Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.transactions
-            .iter()
-            .map(|(account_id, transactions)| (*account_id, transactions))
+        self.iter()
     }
 }
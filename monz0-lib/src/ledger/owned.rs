@@ -0,0 +1,343 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Error, Ledger, Transactions};
+use crate::State;
+
+/// An owned, serialisable counterpart to [`Ledger`].
+///
+/// Unlike a [`Ledger`], an [`OwnedLedger`] doesn't borrow from the [`State`]
+/// it was computed from, so it can be stored, sent between tasks, or written to
+/// disk.
+///
+/// Each [`Transfer`] is assigned a unique dedupe ID when the [`OwnedLedger`] is
+/// created. The dedupe ID is sent with every attempt at the transfer, so that
+/// Monzo will only ever apply it once.
+///
+/// [`State`]: crate::State
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OwnedLedger {
+    /// Transactions, keyed by account ID
+    accounts: BTreeMap<String, OwnedTransactions>,
+}
+
+/// An owned, serialisable counterpart to [`Transactions`]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OwnedTransactions {
+    /// A list of withdrawals from pots into the current account
+    pub withdrawals: Vec<Transfer>,
+
+    /// A list of deposits from the current account into pots
+    pub deposits: Vec<Transfer>,
+}
+
+/// A single transfer between the current account and a pot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transfer {
+    /// The ID of the pot
+    pub pot_id: String,
+
+    /// The name of the pot
+    pub pot_name: String,
+
+    /// The ISO currency code of the pot
+    pub currency: String,
+
+    /// The amount to transfer, in minor units
+    pub amount: u32,
+
+    /// A unique ID for the transfer, used by Monzo to ignore duplicate
+    /// requests
+    pub dedupe_id: String,
+}
+
+impl OwnedLedger {
+    /// Checks whether there are zero transactions in the ledger
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.accounts.values().all(OwnedTransactions::is_empty)
+    }
+
+    /// Returns an iterator over the account IDs and their respective
+    /// [`OwnedTransactions`]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &OwnedTransactions)> {
+        self.accounts
+            .iter()
+            .map(|(account_id, transactions)| (account_id.as_str(), transactions))
+    }
+
+    /// Check that the ledger can be applied to the given [`State`].
+    ///
+    /// This checks that every account and pot in the ledger exists, that no
+    /// pot is left with a negative balance after the withdrawals, and that the
+    /// deposits from each account don't exceed the cash available (including
+    /// the cash withdrawn from pots).
+    ///
+    /// # Errors
+    ///
+    /// Returns the first problem found with the ledger.
+    pub fn validate(&self, state: &State) -> Result<(), Error> {
+        for (account_id, transactions) in self {
+            let account = state
+                .get(account_id)
+                .ok_or_else(|| Error::NotFound(format!("account {} not found", account_id)))?;
+
+            let mut withdrawals: BTreeMap<&str, i64> = BTreeMap::default();
+            for transfer in &transactions.withdrawals {
+                *withdrawals.entry(transfer.pot_id.as_str()).or_default() +=
+                    i64::from(transfer.amount);
+            }
+
+            let find_pot = |pot_id: &str| {
+                account
+                    .pots
+                    .iter()
+                    .find(|pot| pot.id == pot_id && !pot.deleted)
+                    .ok_or_else(|| Error::NotFound(format!("pot {} not found", pot_id)))
+            };
+
+            for (pot_id, withdrawn) in &withdrawals {
+                let pot = find_pot(pot_id)?;
+                if *withdrawn > pot.balance {
+                    return Err(Error::Overdrawn {
+                        pot: pot.name.clone(),
+                        balance: pot.balance,
+                        withdrawals: *withdrawn,
+                    });
+                }
+            }
+
+            for transfer in &transactions.deposits {
+                find_pot(transfer.pot_id.as_str())?;
+            }
+
+            let available = account.balance.balance + withdrawals.values().sum::<i64>();
+            let deposits = transactions
+                .deposits
+                .iter()
+                .map(|transfer| i64::from(transfer.amount))
+                .sum::<i64>();
+
+            if deposits > available {
+                return Err(Error::InsufficientFunds {
+                    account_id: account_id.to_string(),
+                    available,
+                    deposits,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl OwnedTransactions {
+    /// Checks whether there are zero transactions
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.withdrawals.is_empty() && self.deposits.is_empty()
+    }
+
+    /// Returns an iterator over the transfers, with withdrawals as negative
+    /// amounts and deposits as positive amounts. Withdrawals come first.
+    pub fn signed(&self) -> impl Iterator<Item = (&Transfer, i64)> {
+        let withdrawals = self
+            .withdrawals
+            .iter()
+            .map(|transfer| (transfer, -i64::from(transfer.amount)));
+
+        let deposits = self
+            .deposits
+            .iter()
+            .map(|transfer| (transfer, i64::from(transfer.amount)));
+
+        withdrawals.chain(deposits)
+    }
+}
+
+impl From<&Ledger<'_>> for OwnedLedger {
+    fn from(ledger: &Ledger<'_>) -> Self {
+        let mut dedupe_ids = DedupeIds::new();
+
+        let accounts = ledger
+            .into_iter()
+            .map(|(account_id, transactions)| {
+                (
+                    account_id.to_string(),
+                    OwnedTransactions::new(transactions, &mut dedupe_ids),
+                )
+            })
+            .collect();
+
+        Self { accounts }
+    }
+}
+
+impl From<Ledger<'_>> for OwnedLedger {
+    fn from(ledger: Ledger<'_>) -> Self {
+        Self::from(&ledger)
+    }
+}
+
+impl OwnedTransactions {
+    fn new(transactions: &Transactions<'_>, dedupe_ids: &mut DedupeIds) -> Self {
+        let mut transfers = |entries: &[(&monzo::Pot, u32)]| {
+            entries
+                .iter()
+                .map(|(pot, amount)| Transfer {
+                    pot_id: pot.id.clone(),
+                    pot_name: pot.name.clone(),
+                    currency: pot.currency.clone(),
+                    amount: *amount,
+                    dedupe_id: dedupe_ids.next_id(),
+                })
+                .collect()
+        };
+
+        Self {
+            withdrawals: transfers(&transactions.withdrawals),
+            deposits: transfers(&transactions.deposits),
+        }
+    }
+}
+
+/// Generates dedupe IDs for the transfers in a ledger.
+///
+/// The IDs share a random prefix, which is unique to the ledger, followed by a
+/// sequence number.
+struct DedupeIds {
+    prefix: u64,
+    count: usize,
+}
+
+impl DedupeIds {
+    fn new() -> Self {
+        Self {
+            prefix: rand::random(),
+            count: 0,
+        }
+    }
+
+    fn next_id(&mut self) -> String {
+        let id = format!("monz0-{:016x}-{}", self.prefix, self.count);
+        self.count += 1;
+        id
+    }
+}
+
+impl<'a> IntoIterator for &'a OwnedLedger {
+    type Item = (&'a str, &'a OwnedTransactions);
+
+    type IntoIter = impl Iterator<Item = Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OwnedLedger, Transfer};
+    use crate::{
+        ledger::Error,
+        test_support::{pot, state, ACCOUNT_ID},
+        Ledger,
+    };
+
+    #[test]
+    fn from_ledger() {
+        let savings = pot("Savings", 0, None);
+        let bills = pot("Bills", 0, None);
+
+        let mut ledger = Ledger::default();
+        ledger.push(ACCOUNT_ID, &savings, -100).unwrap();
+        ledger.push(ACCOUNT_ID, &bills, 100).unwrap();
+
+        let owned = OwnedLedger::from(&ledger);
+        let (account_id, transactions) = owned.iter().next().unwrap();
+
+        assert_eq!(account_id, ACCOUNT_ID);
+        assert_eq!(
+            transactions.withdrawals,
+            vec![Transfer {
+                pot_id: "pot_Savings".to_string(),
+                pot_name: "Savings".to_string(),
+                currency: "GBP".to_string(),
+                amount: 100,
+                dedupe_id: transactions.withdrawals[0].dedupe_id.clone(),
+            }]
+        );
+        assert_eq!(transactions.deposits.len(), 1);
+        assert_ne!(
+            transactions.withdrawals[0].dedupe_id,
+            transactions.deposits[0].dedupe_id
+        );
+
+        let signed: Vec<_> = transactions.signed().map(|(_, amount)| amount).collect();
+        assert_eq!(signed, vec![-100, 100]);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let savings = pot("Savings", 0, None);
+
+        let mut ledger = Ledger::default();
+        ledger.push(ACCOUNT_ID, &savings, 100).unwrap();
+        let owned = OwnedLedger::from(ledger);
+
+        let raw = serde_yaml::to_string(&owned).unwrap();
+        assert_eq!(serde_yaml::from_str::<OwnedLedger>(&raw).unwrap(), owned);
+    }
+
+    #[test]
+    fn validate() {
+        let state = state(
+            1_000,
+            vec![pot("Savings", 500, None), pot("Bills", 0, None)],
+        );
+        let account = state.get(ACCOUNT_ID).unwrap();
+        let (savings, bills) = (&account.pots[0], &account.pots[1]);
+
+        let mut ledger = Ledger::default();
+        ledger.push(ACCOUNT_ID, savings, -500).unwrap();
+        ledger.push(ACCOUNT_ID, bills, 1_500).unwrap();
+        assert_eq!(OwnedLedger::from(&ledger).validate(&state), Ok(()));
+
+        ledger.push(ACCOUNT_ID, bills, 1).unwrap();
+        assert_eq!(
+            OwnedLedger::from(&ledger).validate(&state),
+            Err(Error::InsufficientFunds {
+                account_id: ACCOUNT_ID.to_string(),
+                available: 1_500,
+                deposits: 1_501,
+            })
+        );
+
+        let mut ledger = Ledger::default();
+        ledger.push(ACCOUNT_ID, savings, -300).unwrap();
+        ledger.push(ACCOUNT_ID, savings, -300).unwrap();
+        assert_eq!(
+            OwnedLedger::from(&ledger).validate(&state),
+            Err(Error::Overdrawn {
+                pot: "Savings".to_string(),
+                balance: 500,
+                withdrawals: 600,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_missing_pot() {
+        let missing = pot("Holiday", 0, None);
+        let state = state(1_000, vec![pot("Savings", 500, None)]);
+
+        let mut ledger = Ledger::default();
+        ledger.push(ACCOUNT_ID, &missing, 100).unwrap();
+
+        assert_eq!(
+            OwnedLedger::from(&ledger).validate(&state),
+            Err(Error::NotFound("pot pot_Holiday not found".to_string()))
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use super::Pot;
+use super::{Error, Pot};
 
 /// Represents a ledger of transactions (deposits and withdrawals) associated
 /// with an account
@@ -15,15 +15,27 @@ impl<'a> Transactions<'a> {
     /// Add a transaction to the ledger
     ///
     /// transactions with '0' value are ignored.
-    pub fn push(&mut self, pot: &'a Pot, amount: i64) {
+    ///
+    /// # Errors
+    ///
+    /// Transfers are limited to the range of a [`u32`]. This method returns an
+    /// [`Error::AmountOutOfRange`] if the magnitude of the amount is too large,
+    /// rather than silently truncating it.
+    pub fn push(&mut self, pot: &'a Pot, amount: i64) -> Result<(), Error> {
         use std::cmp::Ordering;
 
-        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let value = u32::try_from(amount.unsigned_abs()).map_err(|_| Error::AmountOutOfRange {
+            pot: pot.name.clone(),
+            amount,
+        })?;
+
         match Ord::cmp(&amount, &0) {
-            Ordering::Less => self.withdrawals.push((pot, amount.abs() as u32)),
+            Ordering::Less => self.withdrawals.push((pot, value)),
             Ordering::Equal => (),
-            Ordering::Greater => self.deposits.push((pot, amount as u32)),
+            Ordering::Greater => self.deposits.push((pot, value)),
         }
+
+        Ok(())
     }
 
     /// Checks whether there are zero transactions in the ledger
@@ -62,7 +74,7 @@ impl<'a> IntoIterator for &'a Transactions<'a> {
 mod tests {
     use monzo::Pot;
 
-    use super::Transactions;
+    use super::{Error, Transactions};
 
     fn dummy_pot() -> Pot {
         let pot = r#"
@@ -98,13 +110,34 @@ mod tests {
         let mut transactions = Transactions::default();
         let pot = dummy_pot();
 
-        transactions.push(&pot, 100);
+        transactions.push(&pot, 100).unwrap();
         assert!(transactions.deposits.len() == 1);
 
-        transactions.push(&pot, -100);
+        transactions.push(&pot, -100).unwrap();
         assert!(transactions.withdrawals.len() == 1);
 
-        transactions.push(&pot, 0);
+        transactions.push(&pot, 0).unwrap();
         assert!(transactions.len() == 2);
     }
+
+    #[test]
+    fn push_out_of_range() {
+        let mut transactions = Transactions::default();
+        let pot = dummy_pot();
+
+        transactions.push(&pot, i64::from(u32::MAX)).unwrap();
+        transactions.push(&pot, -i64::from(u32::MAX)).unwrap();
+
+        for amount in [i64::from(u32::MAX) + 1, i64::MIN, i64::MAX] {
+            assert_eq!(
+                transactions.push(&pot, amount),
+                Err(Error::AmountOutOfRange {
+                    pot: "Savings".to_string(),
+                    amount
+                })
+            );
+        }
+
+        assert_eq!(transactions.len(), 2);
+    }
 }
@@ -10,13 +10,29 @@
 #![warn(clippy::pedantic)]
 
 pub use monzo::Pot;
-mod ledger;
-pub use ledger::Ledger;
+pub mod apportion;
+pub mod ledger;
+pub use ledger::{Ledger, OwnedLedger};
+pub mod backend;
 mod client;
+pub mod error;
+pub mod limit;
+pub mod money;
+#[doc(inline)]
+pub use limit::RateLimit;
 pub mod state;
 #[doc(inline)]
 pub use state::State;
 pub mod operation;
-pub use client::{Auth, Client};
+pub mod report;
+pub mod retry;
+pub mod validate;
+pub use client::{oauth, Auth, Client};
+#[doc(inline)]
+pub use error::Error;
 #[doc(inline)]
 pub use operation::Operation;
+#[doc(inline)]
+pub use retry::RetryPolicy;
+#[cfg(test)]
+mod test_support;
@@ -0,0 +1,213 @@
+//! Limiting the rate and concurrency of requests to the Monzo API
+//!
+//! See [`RateLimit`].
+
+use std::future::Future;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{Mutex, Semaphore},
+    time::{Duration, Instant},
+};
+
+/// Limits on the requests the [`Client`](crate::Client) makes to the Monzo
+/// API.
+///
+/// The limits are shared by every request the client makes, including
+/// retries. Requests are throttled by a token bucket, which holds up to
+/// `burst` tokens and is refilled at `requests_per_second`.
+///
+/// # Example
+///
+/// ```
+/// use monz0_lib::{backend::InMemory, Client, RateLimit, State};
+///
+/// let limit = RateLimit {
+///     max_concurrent_requests: 2,
+///     ..RateLimit::default()
+/// };
+///
+/// let client = Client::new(InMemory::new(State::default())).with_rate_limit(limit);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct RateLimit {
+    /// The maximum number of requests in flight at once. A value of `0`
+    /// disables the limit.
+    pub max_concurrent_requests: usize,
+
+    /// The sustained rate of requests. A value of `0` disables rate limiting.
+    pub requests_per_second: u32,
+
+    /// The number of requests which can be made at once before the rate limit
+    /// applies
+    pub burst: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            max_concurrent_requests: 4,
+            requests_per_second: 5,
+            burst: 10,
+        }
+    }
+}
+
+impl RateLimit {
+    /// No limits on the rate or concurrency of requests
+    #[must_use]
+    pub fn none() -> Self {
+        Self {
+            max_concurrent_requests: 0,
+            requests_per_second: 0,
+            burst: 0,
+        }
+    }
+}
+
+/// Enforces a [`RateLimit`]
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    limit: RateLimit,
+    permits: Option<Semaphore>,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl From<RateLimit> for Limiter {
+    fn from(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            permits: match limit.max_concurrent_requests {
+                0 => None,
+                permits => Some(Semaphore::new(permits)),
+            },
+            bucket: Mutex::new(Bucket {
+                tokens: f64::from(limit.burst.max(1)),
+                updated: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl Limiter {
+    /// Run a request once it is permitted by the limits
+    pub async fn run<Fut: Future>(&self, request: Fut) -> Fut::Output {
+        let _permit = match &self.permits {
+            Some(permits) => Some(
+                permits
+                    .acquire()
+                    .await
+                    .expect("the semaphore is never closed"),
+            ),
+            None => None,
+        };
+        self.take_token().await;
+
+        request.await
+    }
+
+    /// Wait until a token is available in the bucket, and take it
+    async fn take_token(&self) {
+        if self.limit.requests_per_second == 0 {
+            return;
+        }
+
+        let rate = f64::from(self.limit.requests_per_second);
+        let capacity = f64::from(self.limit.burst.max(1));
+
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens = elapsed.mul_add(rate, bucket.tokens).min(capacity);
+                bucket.updated = now;
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+
+                Duration::from_secs_f64((1.0 - bucket.tokens) / rate)
+            };
+
+            tracing::debug!("rate limited, waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures_util::future::join_all;
+    use tokio::time::{Duration, Instant};
+
+    use super::{Limiter, RateLimit};
+
+    #[tokio::test(start_paused = true)]
+    async fn max_concurrent_requests() {
+        let limiter = Limiter::from(RateLimit {
+            max_concurrent_requests: 2,
+            ..RateLimit::none()
+        });
+
+        let in_flight = AtomicUsize::default();
+        let max_in_flight = AtomicUsize::default();
+
+        join_all((0..10).map(|_| {
+            limiter.run(async {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+            })
+        }))
+        .await;
+
+        assert_eq!(max_in_flight.into_inner(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_per_second() {
+        let limiter = Limiter::from(RateLimit {
+            max_concurrent_requests: 100,
+            requests_per_second: 10,
+            burst: 5,
+        });
+
+        let start = Instant::now();
+        join_all((0..15).map(|_| limiter.run(async {}))).await;
+
+        // the first 5 requests use the burst, the other 10 take a second
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(990), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1100), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn deserialise_yaml() {
+        let raw = "
+        max_concurrent_requests: 1
+        ";
+
+        let limit: RateLimit = serde_yaml::from_str(raw).unwrap();
+        assert_eq!(
+            limit,
+            RateLimit {
+                max_concurrent_requests: 1,
+                ..RateLimit::default()
+            }
+        );
+    }
+}
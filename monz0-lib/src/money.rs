@@ -0,0 +1,436 @@
+//! Amounts of money in configuration
+//!
+//! See [`Amount`].
+
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+use rusty_money::{iso, Money};
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Metadata, Schema, SchemaObject, StringValidation, SubschemaValidation},
+    JsonSchema,
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// An amount of money, as configured for an [`Operation`](crate::Operation).
+///
+/// Amounts are written as strings which make the units and currency explicit:
+///
+/// - `"£100"` or `"£100.50"`, with a leading currency symbol (`£`, `€` or `$`)
+/// - `"100.50 GBP"`, with a trailing ISO currency code
+/// - `"10000p"`, in minor units (pence) of the account's currency
+///
+/// For backwards compatibility, a plain integer is a whole number of major
+/// units (pounds) of the account's currency.
+///
+/// Amounts which name a currency can only be used with accounts in that
+/// currency. Amounts can't be negative.
+///
+/// # Example
+///
+/// ```
+/// use monz0_lib::money::Amount;
+///
+/// let amount: Amount = "100.50 GBP".parse().unwrap();
+/// assert_eq!(amount.minor_units("GBP"), Ok(10_050));
+///
+/// let amount: Amount = serde_yaml::from_str("100").unwrap();
+/// assert_eq!(amount.minor_units("GBP"), Ok(10_000));
+///
+/// assert!(amount.minor_units("EUR").is_ok());
+/// let amount: Amount = "£100".parse().unwrap();
+/// assert!(amount.minor_units("EUR").is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Amount {
+    value: Value,
+    currency: Option<&'static iso::Currency>,
+}
+
+/// The value of an [`Amount`], which can only be converted to minor units once
+/// the currency is known
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Major(i64),
+    Minor(i64),
+}
+
+/// Errors that can occur when parsing or resolving an [`Amount`]
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum Error {
+    /// The amount couldn't be parsed
+    #[error("invalid amount '{0}' (expected an amount like '£100', '100.50 GBP' or '10000p')")]
+    Invalid(String),
+
+    /// The currency of the amount isn't a known ISO currency
+    #[error("unknown currency '{0}'")]
+    UnknownCurrency(String),
+
+    /// The amount has more decimal places than the currency allows
+    #[error("'{0}' has too many decimal places")]
+    TooPrecise(String),
+
+    /// The amount is too large to be represented in minor units
+    #[error("the amount {0} is too large")]
+    TooLarge(Amount),
+
+    /// The amount is in a different currency to the account it is used with
+    #[error("the amount {amount} is in {currency}, but the account is in {expected}")]
+    CurrencyMismatch {
+        /// The configured amount
+        amount: Amount,
+
+        /// The currency of the amount
+        currency: String,
+
+        /// The currency of the account
+        expected: String,
+    },
+}
+
+/// A regular expression matching the strings which may be amounts, for the
+/// JSON schema. Not every match is a valid amount.
+const PATTERN: &str = concat!(
+    r"^\s*(",
+    r"[£€$]\s*[0-9,]+(\.[0-9]+)?",
+    r"|[0-9,]+(\.[0-9]+)? +[A-Za-z]{3}",
+    r"|[0-9]+p",
+    r")\s*$",
+);
+
+/// The symbols which may be used in place of a currency code
+const SYMBOLS: [(&str, &iso::Currency); 3] = [("£", iso::GBP), ("€", iso::EUR), ("$", iso::USD)];
+
+impl Amount {
+    /// An amount in minor units (pence) of the account's currency
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the amount is negative
+    #[must_use]
+    pub fn minor(amount: i64) -> Self {
+        assert!(amount >= 0, "amounts can't be negative");
+        Self {
+            value: Value::Minor(amount),
+            currency: None,
+        }
+    }
+
+    /// An amount in whole major units (pounds) of the account's currency
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the amount is negative
+    #[must_use]
+    pub fn major(amount: i64) -> Self {
+        assert!(amount >= 0, "amounts can't be negative");
+        Self {
+            value: Value::Major(amount),
+            currency: None,
+        }
+    }
+
+    /// The amount in the minor units of the account's currency, given as an ISO
+    /// code (such as `"GBP"`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the amount is in a different currency to the
+    /// account, the account's currency is unknown, or the amount is too large
+    /// to be represented in minor units.
+    pub fn minor_units(&self, currency: &str) -> Result<i64, Error> {
+        if let Some(own) = self.currency {
+            if own.iso_alpha_code != currency {
+                return Err(Error::CurrencyMismatch {
+                    amount: *self,
+                    currency: own.iso_alpha_code.to_string(),
+                    expected: currency.to_string(),
+                });
+            }
+        }
+
+        match self.value {
+            Value::Minor(amount) => Ok(amount),
+            Value::Major(amount) => {
+                let currency = iso::find(currency)
+                    .ok_or_else(|| Error::UnknownCurrency(currency.to_string()))?;
+                amount
+                    .checked_mul(10_i64.pow(currency.exponent))
+                    .ok_or(Error::TooLarge(*self))
+            }
+        }
+    }
+}
+
+impl Default for Amount {
+    fn default() -> Self {
+        Self::major(0)
+    }
+}
+
+impl From<i64> for Amount {
+    /// A whole number of major units (pounds) of the account's currency
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the amount is negative
+    fn from(amount: i64) -> Self {
+        Self::major(amount)
+    }
+}
+
+impl FromStr for Amount {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let trimmed = raw.trim();
+        let invalid = || Error::Invalid(raw.to_string());
+
+        // amounts are never negative, wherever the sign is written
+        if trimmed.contains('-') {
+            return Err(invalid());
+        }
+
+        if let Some(minor) = trimmed.strip_suffix('p') {
+            if minor.ends_with(|c: char| c.is_ascii_digit()) {
+                return minor.parse().map(Self::minor).map_err(|_| invalid());
+            }
+        }
+
+        let symbol = SYMBOLS
+            .iter()
+            .find_map(|(symbol, currency)| Some((trimmed.strip_prefix(symbol)?, *currency)));
+
+        let (amount, currency) = if let Some(parsed) = symbol {
+            parsed
+        } else {
+            let (amount, code) = trimmed.rsplit_once(' ').ok_or_else(invalid)?;
+            let code = code.trim().to_ascii_uppercase();
+            let currency = iso::find(&code).ok_or(Error::UnknownCurrency(code))?;
+            (amount, currency)
+        };
+
+        // amounts are written the same way whatever the currency's locale, with
+        // `,` separating thousands and `.` as the decimal point
+        let digits = amount.trim().replace(',', "");
+        let (major, fraction) = digits.split_once('.').unwrap_or((&digits, "0"));
+        let is_number = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+        if !is_number(major) || !is_number(fraction) {
+            return Err(invalid());
+        }
+
+        let fraction = fraction.trim_end_matches('0');
+        let places = u32::try_from(fraction.len()).unwrap_or(u32::MAX);
+        if places > currency.exponent {
+            return Err(Error::TooPrecise(raw.to_string()));
+        }
+        let fraction = if fraction.is_empty() {
+            0
+        } else {
+            fraction.parse::<i64>().map_err(|_| invalid())?
+        };
+        let minor = major
+            .parse::<i64>()
+            .ok()
+            .and_then(|major| major.checked_mul(10_i64.pow(currency.exponent)))
+            .and_then(|major| major.checked_add(fraction * 10_i64.pow(currency.exponent - places)))
+            .ok_or_else(invalid)?;
+
+        Ok(Self {
+            value: Value::Minor(minor),
+            currency: Some(currency),
+        })
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.value, self.currency) {
+            (Value::Minor(amount), Some(currency)) => write!(
+                f,
+                "{} {}",
+                Money::from_minor(amount, currency).amount(),
+                currency.iso_alpha_code
+            ),
+            (Value::Minor(amount), None) => write!(f, "{}p", amount),
+            (Value::Major(amount), _) => write!(f, "{}", amount),
+        }
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Value::Major(amount) => serializer.serialize_i64(amount),
+            Value::Minor(_) => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = Amount;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an amount like '£100', '100.50 GBP' or '10000p'")
+            }
+
+            fn visit_i64<E: de::Error>(self, amount: i64) -> Result<Amount, E> {
+                if amount < 0 {
+                    return Err(E::invalid_value(de::Unexpected::Signed(amount), &self));
+                }
+                Ok(Amount::major(amount))
+            }
+
+            fn visit_u64<E: de::Error>(self, amount: u64) -> Result<Amount, E> {
+                i64::try_from(amount)
+                    .map(Amount::major)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(amount), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, amount: &str) -> Result<Amount, E> {
+                amount.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+impl JsonSchema for Amount {
+    fn schema_name() -> String {
+        "Amount".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let string = SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(PATTERN.to_string()),
+                ..StringValidation::default()
+            })),
+            ..SchemaObject::default()
+        };
+
+        SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "An amount like '£100', '100.50 GBP' or '10000p', or a whole number of pounds"
+                        .to_string(),
+                ),
+                examples: vec!["£100".into(), "100.50 GBP".into(), "10000p".into()],
+                ..Metadata::default()
+            })),
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![gen.subschema_for::<u64>(), string.into()]),
+                ..SubschemaValidation::default()
+            })),
+            ..SchemaObject::default()
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+    use test_case::test_case;
+
+    use super::{Amount, Error, PATTERN};
+
+    #[test_case("£100" => Ok(10_000); "symbol")]
+    #[test_case("£100.50" => Ok(10_050); "symbol with pence")]
+    #[test_case("£1,000.5" => Ok(100_050); "digit separator")]
+    #[test_case("100.50 GBP" => Ok(10_050); "code")]
+    #[test_case("100 gbp" => Ok(10_000); "lowercase code")]
+    #[test_case("10000p" => Ok(10_000); "pence")]
+    #[test_case("-£5" => Err(Error::Invalid("-£5".to_string())); "negative before symbol")]
+    #[test_case("£-5" => Err(Error::Invalid("£-5".to_string())); "negative")]
+    #[test_case("£99999999999999999999" => Err(Error::Invalid("£99999999999999999999".to_string())); "overflow")]
+    #[test_case("-5 GBP" => Err(Error::Invalid("-5 GBP".to_string())); "negative with code")]
+    #[test_case("-500p" => Err(Error::Invalid("-500p".to_string())); "negative pence")]
+    #[test_case("100" => Err(Error::Invalid("100".to_string())); "no currency")]
+    #[test_case("100.5p" => Err(Error::Invalid("100.5p".to_string())); "fractional pence")]
+    #[test_case("£100.505" => Err(Error::TooPrecise("£100.505".to_string())); "too precise")]
+    #[test_case("100 XYZ" => Err(Error::UnknownCurrency("XYZ".to_string())); "unknown currency")]
+    fn parse(raw: &str) -> Result<i64, Error> {
+        raw.parse::<Amount>()?.minor_units("GBP")
+    }
+
+    #[test_case("€1,000", "EUR" => Ok(100_000); "euro digit separator")]
+    #[test_case("€20.50", "EUR" => Ok(2_050); "euro with cents")]
+    #[test_case("20.50 EUR", "EUR" => Ok(2_050); "euro code")]
+    #[test_case("$1,000.25", "USD" => Ok(100_025); "dollar digit separator")]
+    #[test_case("1,000.25 usd", "USD" => Ok(100_025); "dollar code")]
+    #[test_case("€20,50", "EUR" => Ok(205_000); "euro comma is a separator")]
+    #[test_case("€20.505", "EUR" => Err(Error::TooPrecise("€20.505".to_string())); "euro too precise")]
+    #[test_case("1.5 JPY", "JPY" => Err(Error::TooPrecise("1.5 JPY".to_string())); "no minor units")]
+    #[test_case("€20.", "EUR" => Err(Error::Invalid("€20.".to_string())); "no decimals")]
+    #[test_case("€1.2.3", "EUR" => Err(Error::Invalid("€1.2.3".to_string())); "two decimal points")]
+    fn parse_currency(raw: &str, currency: &str) -> Result<i64, Error> {
+        raw.parse::<Amount>()?.minor_units(currency)
+    }
+
+    #[test_case("£100", true; "symbol")]
+    #[test_case("£-5", false; "negative")]
+    #[test_case("£1,000.5", true; "digit separator")]
+    #[test_case(" 100.50 gbp ", true; "code")]
+    #[test_case("10000p", true; "pence")]
+    #[test_case("100", false; "no currency")]
+    #[test_case("-£5", false; "negative before symbol")]
+    #[test_case("100.5p", false; "fractional pence")]
+    #[test_case("100GBP", false; "no space before code")]
+    fn schema_pattern(raw: &str, valid: bool) {
+        assert_eq!(Regex::new(PATTERN).unwrap().is_match(raw), valid);
+        assert_eq!(raw.parse::<Amount>().is_ok(), valid);
+    }
+
+    #[test]
+    fn too_large() {
+        let amount = Amount::major(i64::MAX / 10);
+
+        assert_eq!(amount.minor_units("GBP"), Err(Error::TooLarge(amount)));
+        assert_eq!(amount.minor_units("JPY"), Ok(i64::MAX / 10));
+    }
+
+    #[test]
+    #[should_panic(expected = "amounts can't be negative")]
+    fn negative() {
+        let _ = Amount::major(-1);
+    }
+
+    #[test]
+    fn currency_mismatch() {
+        let amount: Amount = "€20".parse().unwrap();
+
+        assert_eq!(amount.minor_units("EUR"), Ok(2_000));
+        assert_eq!(
+            amount.minor_units("GBP").unwrap_err().to_string(),
+            "the amount 20.00 EUR is in EUR, but the account is in GBP"
+        );
+    }
+
+    #[test_case("100" => Ok(10_000); "integer")]
+    #[test_case("'£100'" => Ok(10_000); "string")]
+    #[test_case("'100'" => Err(()); "string without currency")]
+    #[test_case("'10000p'" => Ok(10_000); "pence")]
+    #[test_case("100.5" => Err(()); "float")]
+    #[test_case("-100" => Err(()); "negative")]
+    fn deserialise_yaml(raw: &str) -> Result<i64, ()> {
+        let amount: Amount = serde_yaml::from_str(raw).map_err(|_| ())?;
+        amount.minor_units("GBP").map_err(|_| ())
+    }
+
+    #[test_case(Amount::major(100) => "100\n"; "major")]
+    #[test_case(Amount::minor(50) => "50p\n"; "minor")]
+    #[test_case("£1.5".parse().unwrap() => "1.50 GBP\n"; "currency")]
+    fn serialise_yaml(amount: Amount) -> String {
+        serde_yaml::to_string(&amount)
+            .unwrap()
+            .trim_start_matches("---\n")
+            .to_string()
+    }
+}
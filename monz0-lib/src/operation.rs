@@ -1,13 +1,23 @@
 //! Operations on Monzo pots
 
-use crate::{ledger::Ledger, State};
+use monzo::Pot;
+
+use crate::{ledger::Ledger, validate::PotRef, State};
 
 /// 'Sweep' operation
 pub mod sweep;
 #[doc(inline)]
 pub use sweep::Sweep;
-// mod ratio;
-// pub use ratio::Ratio;
+
+/// 'Ratio' operation
+pub mod ratio;
+#[doc(inline)]
+pub use ratio::Ratio;
+
+/// 'Top-up' operation
+pub mod top_up;
+#[doc(inline)]
+pub use top_up::TopUp;
 
 /// Represents an operation that may be applied to the pots of an account
 pub trait Operation {
@@ -19,6 +29,19 @@ pub trait Operation {
     /// The name of the operation. Used for logging and pretty-printing
     const NAME: &'static str;
 
+    /// The IDs of the accounts that the operation reads from and transfers
+    /// between.
+    ///
+    /// Only these accounts need to be present in the [`State`] passed to
+    /// [`transactions`](Self::transactions).
+    fn account_ids(&self) -> Vec<&str>;
+
+    /// The pots that the operation uses, in the order they're configured.
+    ///
+    /// These can be [checked](crate::validate) without generating any
+    /// transactions.
+    fn pots(&self) -> Vec<PotRef<'_>>;
+
     /// Given an account state, generate a list of transactions to apply to that
     /// account.
     ///
@@ -28,3 +51,28 @@ pub trait Operation {
     /// define the appropriate error type for the operation.
     fn transactions<'a>(&'a self, state: &'a State) -> Result<Ledger, Self::Err>;
 }
+
+/// Normalise a pot name for comparison, by removing non-ASCII characters (such
+/// as emojis), capitalisation, and leading/trailing whitespace.
+pub(crate) fn normalise(name: &str) -> String {
+    let processed: String = name
+        .chars()
+        .filter(char::is_ascii)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    processed.trim().to_string()
+}
+
+/// Returns an iterator over the pots that belong to the given account and have
+/// not been deleted.
+fn active_pots<'a>(pots: &'a [Pot], account_id: &'a str) -> impl Iterator<Item = &'a Pot> {
+    pots.iter()
+        .filter(|pot| !pot.deleted)
+        .filter(move |pot| pot.current_account_id == account_id)
+}
+
+/// Find an active pot belonging to the given account by its (normalised) name
+fn find_pot<'a>(pots: &'a [Pot], account_id: &'a str, name: &str) -> Option<&'a Pot> {
+    let name = normalise(name);
+    active_pots(pots, account_id).find(|pot| normalise(&pot.name) == name)
+}
@@ -1,69 +1,288 @@
-use crate::{
-    client::State,
-    operation::{Operation},
-    transactions::Transactions,
-};
+use indexmap::IndexMap;
 use monzo::Pot;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
-use super::NotFoundError;
+use super::find_pot;
+use crate::{
+    apportion::{apportion, Remainder},
+    ledger::Ledger,
+    money::Amount,
+    operation::Operation,
+    validate::PotRef,
+    State,
+};
+
+/// Errors that can occur when processing a [`Ratio`] operation
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum Error {
+    /// a [`NotFound`](Self::NotFound) is returned when an account or pot
+    /// configured in the [`Ratio`] operation cannot be found in the monzo
+    /// [`State`]
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    /// The weights of the configured pots must sum to a non-zero value
+    #[error("the total weight of the pots must be greater than zero")]
+    ZeroWeight,
+
+    /// A configured amount couldn't be used with the account
+    #[error(transparent)]
+    Money(#[from] crate::money::Error),
 
-#[derive(Debug, Deserialize, Serialize)]
+    /// The operation generated a transaction that couldn't be added to the
+    /// ledger
+    #[error(transparent)]
+    Ledger(#[from] crate::ledger::Error),
+}
+
+/// A [`Ratio`] operation splits any spare cash in the current account (above
+/// the account goal) between a set of pots, according to their relative
+/// weights.
+///
+/// # Example
+///
+/// ```
+/// use monz0_lib::operation::Ratio;
+///
+/// let ratio = Ratio::new("ACCOUNT_ID".into(), 100)
+///     .with_pot("savings".into(), 2)
+///     .with_pot("holiday".into(), 1);
+/// ```
+///
+/// The ratio operation also implements [`serde::Deserialize`], and
+/// [`schemars::JsonSchema`] to describe its config. Amounts are written as
+/// described in [`Amount`].
+///
+/// ```
+/// use monz0_lib::operation::Ratio;
+///
+/// let config = r#"
+/// account_goal: £100
+///
+/// pots:
+///   savings: 2
+///   holiday: 1
+/// "#;
+///
+/// let ratio: Ratio = serde_yaml::from_str(config).unwrap();
+/// ```
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Ratio {
+    /// The ID of the account whose spare cash should be split
     #[serde(default)]
-    current_account_id: String,
+    account_id: String,
+
+    /// The goal amount of the current account itself. Only cash above this
+    /// amount is split between the pots.
+    #[serde(default, alias = "current_account_goal")]
+    account_goal: Amount,
+
+    /// A map of pot names to their relative weights
+    ///
+    /// When determining the pots, the names are normalised by removing emojis,
+    /// normalising capitalisation, and then stripping any leading or trailing
+    /// whitespace.
+    pots: IndexMap<String, u32>,
+
+    /// Where to allocate the pennies left over after splitting the spare cash.
+    ///
+    /// Defaults to [`Remainder::LargestRemainder`].
     #[serde(default)]
-    current_account_goal: Option<f32>,
-    pots: HashMap<String, u32>,
+    remainder: Remainder,
+}
+
+impl Ratio {
+    /// Create a new [`Ratio`] operation
+    #[must_use]
+    pub fn new(account_id: String, account_goal: impl Into<Amount>) -> Self {
+        Self {
+            account_id,
+            account_goal: account_goal.into(),
+            pots: IndexMap::default(),
+            remainder: Remainder::default(),
+        }
+    }
+
+    /// Add a pot to the ratio operation, with the given relative weight
+    ///
+    /// Pot names are normalised before comparison, by removing non-ASCII
+    /// characters, capitalisation, and leading/trailing whitespace.
+    #[must_use]
+    pub fn with_pot(mut self, name: String, weight: u32) -> Self {
+        self.pots.insert(name, weight);
+        self
+    }
+
+    /// Set the policy used to allocate the pennies left over after splitting
+    /// the spare cash
+    #[must_use]
+    pub fn with_remainder(mut self, remainder: Remainder) -> Self {
+        self.remainder = remainder;
+        self
+    }
 }
 
 impl Operation for Ratio {
-    type Err = NotFoundError;
-    fn name(&self) -> &'static str {
-        "Ratio"
+    type Err = Error;
+
+    const NAME: &'static str = "Ratio";
+
+    fn account_ids(&self) -> Vec<&str> {
+        vec![&self.account_id]
     }
 
-    fn account_id(&self) -> &str {
-        &self.current_account_id
+    fn pots(&self) -> Vec<PotRef<'_>> {
+        self.pots
+            .keys()
+            .map(|name| PotRef {
+                account_id: &self.account_id,
+                name,
+                needs_goal: false,
+            })
+            .collect()
     }
 
-    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
-    fn transactions<'a>(&'a self, state: &'a State) -> Result<Transactions, Self::Err> {
-        let spare_cash = state.balance.balance;
+    fn transactions<'a>(&'a self, state: &'a State) -> Result<Ledger<'a>, Self::Err> {
+        let account_state = state
+            .get(&self.account_id)
+            .ok_or_else(|| Error::NotFound(format!("account {} not found", self.account_id)))?;
+
+        let pots = self
+            .pots
+            .iter()
+            .map(|(name, weight)| {
+                find_pot(&account_state.pots, &self.account_id, name)
+                    .map(|pot| (pot, *weight))
+                    .ok_or_else(|| Error::NotFound(format!("failed to find pot: {}", name)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let account_goal = self
+            .account_goal
+            .minor_units(&account_state.balance.currency)?;
+        let spare_cash = account_state.balance.balance - account_goal;
 
-        let denominator: u32 = self.pots.values().sum();
+        let mut ledger = Ledger::default();
 
-        let deposits = self.pots.iter().map(|(name, numerator)| {
-            let pot = find_pot_by_name(&state.pots, name).unwrap();
-            let deposit: i64 = ((f64::from(*numerator)) / (f64::from(denominator))
-                * spare_cash as f64)
-                .floor() as i64;
-            (pot, deposit)
-        });
+        for (pot, amount) in calculate_deposits(spare_cash, pots, self.remainder)? {
+            ledger.push(&self.account_id, pot, amount)?;
+        }
 
-        Ok(deposits.collect())
+        Ok(ledger)
     }
 }
 
-fn find_pot_by_name<'a>(pots: &'a [Pot], name: &'a str) -> Option<&'a Pot> {
-    pots.iter().find(|pot| pot.name == name)
+/// Split the spare cash between the pots according to their weights.
+///
+/// The deposits always sum to exactly the spare cash.
+fn calculate_deposits(
+    spare_cash: i64,
+    pots: Vec<(&Pot, u32)>,
+    remainder: Remainder,
+) -> Result<Vec<(&Pot, i64)>, Error> {
+    if spare_cash <= 0 {
+        return Ok(Vec::default());
+    }
+
+    let (pots, weights): (Vec<_>, Vec<_>) = pots.into_iter().unzip();
+
+    let deposits = apportion(spare_cash, &weights, remainder).ok_or(Error::ZeroWeight)?;
+
+    Ok(pots.into_iter().zip(deposits).collect())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{pot, state, ACCOUNT_ID};
 
     #[test]
     fn deserialise_yaml() {
-        let raw = r#"
+        let raw = "
         current_account_goal: 10000
 
         pots:
           savings: 2
           holiday: 1
-        "#;
+        ";
 
         serde_yaml::from_str::<Ratio>(raw).unwrap();
     }
+
+    #[test]
+    fn transactions() {
+        let state = state(
+            40_000,
+            vec![pot("🏦 Savings", 0, None), pot("Holiday", 100, None)],
+        );
+
+        let ratio = Ratio::new(ACCOUNT_ID.to_string(), 100)
+            .with_pot("savings".to_string(), 2)
+            .with_pot("holiday".to_string(), 1);
+
+        let ledger = ratio.transactions(&state).unwrap();
+        let (account_id, transactions) = ledger.into_iter().next().unwrap();
+        assert_eq!(account_id, ACCOUNT_ID);
+
+        let deposits: Vec<_> = transactions
+            .deposits
+            .iter()
+            .map(|(pot, amount)| (pot.name.as_str(), *amount))
+            .collect();
+        assert_eq!(deposits, vec![("🏦 Savings", 20_000), ("Holiday", 10_000)]);
+        assert!(transactions.withdrawals.is_empty());
+    }
+
+    #[test]
+    fn no_lost_pennies() {
+        let state = state(
+            10_000,
+            vec![pot("A", 0, None), pot("B", 0, None), pot("C", 0, None)],
+        );
+
+        let ratio = Ratio::new(ACCOUNT_ID.to_string(), 0)
+            .with_pot("a".to_string(), 1)
+            .with_pot("b".to_string(), 1)
+            .with_pot("c".to_string(), 1)
+            .with_remainder(Remainder::Last);
+
+        let ledger = ratio.transactions(&state).unwrap();
+        let (_, transactions) = ledger.into_iter().next().unwrap();
+
+        let deposits: Vec<_> = transactions
+            .deposits
+            .iter()
+            .map(|(pot, amount)| (pot.name.as_str(), *amount))
+            .collect();
+        assert_eq!(deposits, vec![("A", 3333), ("B", 3333), ("C", 3334)]);
+    }
+
+    #[test]
+    fn below_account_goal() {
+        let state = state(5_000, vec![pot("Savings", 0, None)]);
+        let ratio = Ratio::new(ACCOUNT_ID.to_string(), 100).with_pot("savings".to_string(), 1);
+
+        assert!(ratio.transactions(&state).unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_pot() {
+        let state = state(5_000, vec![pot("Savings", 0, None)]);
+        let ratio = Ratio::new(ACCOUNT_ID.to_string(), 0).with_pot("holiday".to_string(), 1);
+
+        assert_eq!(
+            ratio.transactions(&state).unwrap_err(),
+            Error::NotFound("failed to find pot: holiday".to_string())
+        );
+    }
+
+    #[test]
+    fn zero_weight() {
+        let state = state(5_000, vec![pot("Savings", 0, None)]);
+        let ratio = Ratio::new(ACCOUNT_ID.to_string(), 0).with_pot("savings".to_string(), 0);
+
+        assert_eq!(ratio.transactions(&state).unwrap_err(), Error::ZeroWeight);
+    }
 }
@@ -1,9 +1,11 @@
 use std::cmp::Ordering;
 
 use monzo::Pot;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{ledger::Ledger, operation::Operation, State};
+use super::normalise;
+use crate::{ledger::Ledger, money::Amount, operation::Operation, validate::PotRef, State};
 
 /// Errors that can occur when processing a [`Sweep`] operation
 #[derive(Debug, thiserror::Error, PartialEq)]
@@ -15,43 +17,75 @@ pub enum Error {
     NotFound(String),
 
     /// The [`Sweep`] operation can only be used with pots that have a goal
-    /// amount set
+    /// amount set, either in Monzo or in the configuration
     #[error("Pot '{0}' has no 'goal amount' set")]
     NoPotGoal(String),
+
+    /// A pot cannot be configured with both a target and as unbounded
+    #[error("Pot '{0}' cannot have both a 'target' and be 'unbounded'")]
+    ConflictingTarget(String),
+
+    /// Only the last pot can be unbounded, since it takes all the remaining
+    /// cash and would leave nothing for the pots after it
+    #[error("Pot '{0}' is unbounded, so it must be the last pot in the sweep")]
+    UnboundedNotLast(String),
+
+    /// A configured amount couldn't be used with the account
+    #[error(transparent)]
+    Money(#[from] crate::money::Error),
+
+    /// The operation generated a transaction that couldn't be added to the
+    /// ledger
+    #[error(transparent)]
+    Ledger(#[from] crate::ledger::Error),
 }
 
 /// A [`Sweep`] operation moves through a list of pots, sweeping any extra money
 /// above the goal amount into the next pot down the list.
 ///
-/// It is an error to sweep pots that do not have a goal amount set.
+/// By default, each pot is filled up to the goal amount set in Monzo. The goal
+/// can be overridden (or provided, for pots without a goal) by configuring a
+/// `target` for the pot. A pot may instead be marked as `unbounded`, in which
+/// case it accepts all the remaining cash. This is useful for a final
+/// 'catch-all' pot, and only the last pot may be unbounded.
+///
+/// It is an error to sweep pots that have neither a goal amount nor a
+/// configured target.
 ///
 /// # Example
 ///
 /// ```
-/// use monz0_lib::Sweep;
+/// use monz0_lib::operation::Sweep;
 ///
-/// let sweep = Sweep::new("ACCCOUNT_ID".into(), 100.0).with_pot(savings);
+/// let sweep = Sweep::new("ACCOUNT_ID".into(), 100)
+///     .with_pot("bills".into())
+///     .with_pot_target("allowance".into(), 50)
+///     .with_unbounded_pot("savings".into());
 /// ```
 ///
-/// The sweep operation also implements [`serde::Deserialize`]
+/// The sweep operation also implements [`serde::Deserialize`], and
+/// [`schemars::JsonSchema`] to describe its config. Amounts are written as
+/// described in [`Amount`].
 ///
 /// ```
-/// use monz0_lib::Sweep;
+/// use monz0_lib::operation::Sweep;
 ///
 /// let config = r#"
-/// account_goal: 10000
+/// account_goal: £100
 ///
 /// pots:
 ///  - bills
 ///  - lottery
-///  - allowance
+///  - name: allowance
+///    target: 50.00 GBP
 ///  - student loan
-///  - savings
+///  - name: savings
+///    unbounded: true
 /// "#;
 ///
 /// let sweep: Sweep = serde_yaml::from_str(config).unwrap();
 /// ```
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Sweep {
     /// The ID of the account to be swept
@@ -60,23 +94,91 @@ pub struct Sweep {
 
     /// The goal amount of the current account itself
     #[serde(default)]
-    account_goal: i64,
+    account_goal: Amount,
 
-    /// A list of names of pots that should be swept, in order
+    /// A list of pots that should be swept, in order
     ///
     /// When determining the pots, the names are normalised by removing emojis,
     /// normalising capitalisation, and then stripping any leading or trailing
     /// whitespace.
-    pots: Vec<String>,
+    pots: Vec<SweepPot>,
+}
+
+/// A pot to be swept, either given by name alone or with a custom target
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+enum SweepPot {
+    /// The name of the pot. The goal amount set in Monzo is used as the target.
+    Name(String),
+
+    /// A pot with a custom target
+    Config(PotConfig),
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct PotConfig {
+    /// The name of the pot
+    name: String,
+
+    /// The target balance of the pot, overriding the goal amount set in Monzo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target: Option<Amount>,
+
+    /// If true, the pot has no upper limit, and accepts all remaining cash
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    unbounded: bool,
+}
+
+impl SweepPot {
+    fn name(&self) -> &str {
+        match self {
+            Self::Name(name) => name,
+            Self::Config(config) => &config.name,
+        }
+    }
+
+    /// Resolve the target of the pot, falling back to the goal amount set in
+    /// Monzo
+    fn target(&self, pot: &Pot) -> Result<Target, Error> {
+        match self {
+            Self::Config(PotConfig {
+                name,
+                target: Some(_),
+                unbounded: true,
+            }) => Err(Error::ConflictingTarget(name.clone())),
+            Self::Config(PotConfig {
+                unbounded: true, ..
+            }) => Ok(Target::Unbounded),
+            Self::Config(PotConfig {
+                target: Some(target),
+                ..
+            }) => Ok(Target::Bounded(target.minor_units(&pot.currency)?)),
+            _ => pot
+                .goal_amount
+                .map(Target::Bounded)
+                .ok_or_else(|| Error::NoPotGoal(pot.name.clone())),
+        }
+    }
+}
+
+/// The balance that a pot is swept towards
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    /// The pot should hold exactly this amount (in minor units)
+    Bounded(i64),
+
+    /// The pot accepts any amount
+    Unbounded,
 }
 
 impl Sweep {
     /// Create a new [`Sweep`] operation
     #[must_use]
-    pub fn new(account_id: String, account_goal: i64) -> Self {
+    pub fn new(account_id: String, account_goal: impl Into<Amount>) -> Self {
         Self {
             account_id,
-            account_goal,
+            account_goal: account_goal.into(),
             pots: Vec::default(),
         }
     }
@@ -89,7 +191,31 @@ impl Sweep {
     /// whitespace.
     #[must_use]
     pub fn with_pot(mut self, name: String) -> Self {
-        self.pots.push(name);
+        self.pots.push(SweepPot::Name(name));
+        self
+    }
+
+    /// Add a pot to the sweep operation, with a target balance that overrides
+    /// the goal amount set in Monzo
+    #[must_use]
+    pub fn with_pot_target(mut self, name: String, target: impl Into<Amount>) -> Self {
+        self.pots.push(SweepPot::Config(PotConfig {
+            name,
+            target: Some(target.into()),
+            unbounded: false,
+        }));
+        self
+    }
+
+    /// Add an unbounded pot to the sweep operation. The pot accepts all the
+    /// cash remaining after the preceding pots have been filled.
+    #[must_use]
+    pub fn with_unbounded_pot(mut self, name: String) -> Self {
+        self.pots.push(SweepPot::Config(PotConfig {
+            name,
+            target: None,
+            unbounded: true,
+        }));
         self
     }
 }
@@ -99,20 +225,46 @@ impl Operation for Sweep {
 
     const NAME: &'static str = "Sweep";
 
+    fn account_ids(&self) -> Vec<&str> {
+        vec![&self.account_id]
+    }
+
+    fn pots(&self) -> Vec<PotRef<'_>> {
+        self.pots
+            .iter()
+            .map(|pot| PotRef {
+                account_id: &self.account_id,
+                name: pot.name(),
+                needs_goal: matches!(
+                    pot,
+                    SweepPot::Name(_)
+                        | SweepPot::Config(PotConfig {
+                            target: None,
+                            unbounded: false,
+                            ..
+                        })
+                ),
+            })
+            .collect()
+    }
+
     fn transactions<'a>(&'a self, state: &'a State) -> Result<Ledger<'a>, Self::Err> {
         let account_state = state
             .get(&self.account_id)
             .ok_or_else(|| Error::NotFound(format!("account {} not found", self.account_id)))?;
         let balance = account_state.balance.balance;
+        let account_goal = self
+            .account_goal
+            .minor_units(&account_state.balance.currency)?;
 
         let pots = sort_and_filter_pots(&self.account_id, &account_state.pots, &self.pots)?;
 
-        let transactions = calculate_transactions(balance, self.account_goal * 100, pots);
+        let transactions = calculate_transactions(balance, account_goal, pots);
 
         let mut ledger = Ledger::default();
 
         for (pot, amount) in transactions {
-            ledger.push(&self.account_id, pot, amount);
+            ledger.push(&self.account_id, pot, amount)?;
         }
 
         Ok(ledger)
@@ -122,7 +274,7 @@ impl Operation for Sweep {
 fn calculate_transactions<'a>(
     current_account_balance: i64,
     current_account_goal: i64,
-    pots: impl IntoIterator<Item = &'a Pot>,
+    pots: impl IntoIterator<Item = (&'a Pot, Target)>,
 ) -> Vec<Transaction<'a>> {
     let (withdrawals, remainder) = withdrawals(pots);
 
@@ -136,9 +288,9 @@ fn calculate_transactions<'a>(
             break;
         }
 
-        let deposit = match spare_cash.cmp(&diff) {
-            Ordering::Less | Ordering::Equal => spare_cash,
-            Ordering::Greater => diff,
+        let deposit = match diff {
+            Some(diff) if diff < spare_cash => diff,
+            _ => spare_cash,
         };
 
         spare_cash -= deposit;
@@ -154,89 +306,74 @@ fn calculate_transactions<'a>(
 type Transaction<'a> = (&'a Pot, i64);
 
 /// Returns the set of [`Transaction`]s needed to shift the balance of each
-/// [`Pot`] to its respective goal amount.
+/// [`Pot`] to its respective target.
 ///
-/// The results are partitioned into
-/// withdrawals and deposits respectively. Note that withdrawals should always
-/// be possible, but deposits are constrained by the available spare balance.
+/// The results are partitioned into withdrawals and the remaining pots with
+/// room for deposits respectively. Note that withdrawals should always be
+/// possible, but deposits are constrained by the available spare balance.
+/// Unbounded pots have unlimited room for deposits (represented by [`None`]).
 /// Zero-value transactions are ignored.
 fn withdrawals<'a>(
-    pots: impl IntoIterator<Item = &'a Pot>,
-) -> (Vec<Transaction<'a>>, Vec<Transaction<'a>>) {
-    pots.into_iter()
-        .filter(|pot| pot.diff_unchecked() != 0)
-        .map(|pot| {
-            let diff = pot.diff_unchecked();
-            (pot, diff)
-        })
-        .partition(|(_pot, diff)| diff < &0)
+    pots: impl IntoIterator<Item = (&'a Pot, Target)>,
+) -> (Vec<Transaction<'a>>, Vec<(&'a Pot, Option<i64>)>) {
+    let mut withdrawals = Vec::default();
+    let mut remainder = Vec::default();
+
+    for (pot, target) in pots {
+        match target {
+            Target::Bounded(goal) => match (goal - pot.balance).cmp(&0) {
+                Ordering::Less => withdrawals.push((pot, goal - pot.balance)),
+                Ordering::Equal => (),
+                Ordering::Greater => remainder.push((pot, Some(goal - pot.balance))),
+            },
+            Target::Unbounded => remainder.push((pot, None)),
+        }
+    }
+
+    (withdrawals, remainder)
 }
 
+/// Find the configured pots in the given account, in order, along with their
+/// resolved [`Target`]s.
+///
+/// Only the configured pots are validated. An unbounded pot anywhere but last
+/// is rejected, since the pots after it would never receive anything.
 fn sort_and_filter_pots<'a>(
-    account_id: &str,
+    account_id: &'a str,
     pots: &'a [monzo::Pot],
-    pot_names: &'a [String],
-) -> Result<Vec<&'a Pot>, Error> {
-    fn normalise(name: &str) -> String {
-        let processed: String = name
-            .chars()
-            .filter(char::is_ascii)
-            .map(|c| c.to_ascii_lowercase())
-            .collect();
-        processed.trim().to_string()
-    }
-
+    sweep_pots: &'a [SweepPot],
+) -> Result<Vec<(&'a Pot, Target)>, Error> {
     // Filter out any pots that are 'deleted' or where the account id doesn't match
     // the configured one
-    let mut active_pots = pots
-        .iter()
-        // Filter out any pots that are 'deleted'
-        .filter(|pot| !pot.deleted)
-        // Filter out any pots where the account id doesn't match
-        .filter(|pot| pot.current_account_id == account_id)
-        // Check the pot has a goal set
-        .map(|pot| {
-            match pot.goal_amount {
-                Some(_) => Ok(pot),
-                None => Err(Error::NoPotGoal(pot.name.clone())),
-            }
-        })
-        .collect::<Result<Vec<_>, Error>>()?;
+    let mut active_pots: Vec<_> = super::active_pots(pots, account_id).collect();
 
     let mut info = Vec::default();
 
-    for name in pot_names {
+    for (position, sweep_pot) in sweep_pots.iter().enumerate() {
+        let name = sweep_pot.name();
         let index = active_pots
             .iter()
             .position(|pot| normalise(&pot.name) == normalise(name))
             .ok_or_else(|| Error::NotFound(format!("failed to find pot: {}", name)))?;
 
-        info.push(active_pots.remove(index));
+        let pot = active_pots.remove(index);
+        let target = sweep_pot.target(pot)?;
+        if target == Target::Unbounded && position + 1 < sweep_pots.len() {
+            return Err(Error::UnboundedNotLast(pot.name.clone()));
+        }
+
+        info.push((pot, target));
     }
 
     Ok(info)
 }
 
-trait PotExt {
-    /// the difference between a [`Pot`]s current balance and its goal balance
-    ///
-    /// # Panics
-    ///
-    /// This method will panic if the [`Pot`] doesn't have a goal amount set
-    fn diff_unchecked(&self) -> i64;
-}
-
-impl PotExt for Pot {
-    fn diff_unchecked(&self) -> i64 {
-        self.goal_amount.unwrap() - self.balance
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use test_case::test_case;
 
     use super::*;
+    use crate::test_support::{pot, state, ACCOUNT_ID};
 
     #[test]
     fn deserialise_yaml() {
@@ -258,8 +395,179 @@ mod tests {
     fn sort_and_filter_pots<'a>(
         account_id: &'a str,
         pots: &'a [monzo::Pot],
-        pot_names: &'a [String],
-    ) -> Result<Vec<&'a Pot>, Error> {
-        super::super::sort_and_filter_pots(account_id, pots, pot_names)
+        sweep_pots: &'a [SweepPot],
+    ) -> Result<Vec<(&'a Pot, Target)>, Error> {
+        super::super::sort_and_filter_pots(account_id, pots, sweep_pots)
+    }
+
+    #[test]
+    fn schema() {
+        let schema = serde_yaml::to_value(schemars::schema_for!(Sweep)).unwrap();
+
+        assert_eq!(schema["required"][0], "pots");
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(
+            schema["properties"]["account_goal"]["allOf"][0]["$ref"],
+            "#/definitions/Amount"
+        );
+        assert!(schema["definitions"]["SweepPot"]["anyOf"].is_sequence());
+    }
+
+    #[test]
+    fn deserialise_pot_targets() {
+        let raw = "
+        pots:
+         - bills
+         - name: savings
+           target: 5000
+         - name: catch all
+           unbounded: true
+        ";
+
+        let sweep = serde_yaml::from_str::<Sweep>(raw).unwrap();
+        assert!(matches!(&sweep.pots[0], SweepPot::Name(name) if name == "bills"));
+        assert!(matches!(
+            &sweep.pots[1],
+            SweepPot::Config(PotConfig {
+                target: Some(target),
+                unbounded: false,
+                ..
+            }) if *target == Amount::major(5000)
+        ));
+        assert!(matches!(
+            &sweep.pots[2],
+            SweepPot::Config(PotConfig {
+                target: None,
+                unbounded: true,
+                ..
+            })
+        ));
+    }
+
+    fn transactions(
+        balance: i64,
+        pots: Vec<Pot>,
+        sweep: &Sweep,
+    ) -> Result<Vec<(String, i64)>, Error> {
+        let state = state(balance, pots);
+        let ledger = sweep.transactions(&state)?;
+
+        let mut transactions = Vec::default();
+        for (_account_id, account_transactions) in &ledger {
+            transactions.extend(
+                account_transactions
+                    .into_iter()
+                    .map(|(pot, amount)| (pot.name.clone(), amount)),
+            );
+        }
+        Ok(transactions)
+    }
+
+    #[test]
+    fn pot_target_overrides_goal() {
+        let sweep = Sweep::new(ACCOUNT_ID.to_string(), 100)
+            .with_pot_target("bills".to_string(), 20)
+            .with_pot("savings".to_string());
+
+        let pots = vec![
+            pot("Bills", 5_000, Some(5_000)),
+            pot("Savings", 0, Some(100_000)),
+        ];
+
+        assert_eq!(
+            transactions(10_000, pots, &sweep),
+            Ok(vec![
+                ("Bills".to_string(), -3_000),
+                ("Savings".to_string(), 3_000)
+            ])
+        );
+    }
+
+    #[test]
+    fn unbounded_pot_takes_the_remainder() {
+        let sweep = Sweep::new(ACCOUNT_ID.to_string(), 100)
+            .with_pot("bills".to_string())
+            .with_unbounded_pot("savings".to_string());
+
+        let pots = vec![
+            pot("Bills", 0, Some(5_000)),
+            pot("Savings", 1_000_000, None),
+        ];
+
+        assert_eq!(
+            transactions(100_000, pots, &sweep),
+            Ok(vec![
+                ("Bills".to_string(), 5_000),
+                ("Savings".to_string(), 85_000)
+            ])
+        );
+    }
+
+    #[test]
+    fn unbounded_pot_must_be_last() {
+        let sweep = Sweep::new(ACCOUNT_ID.to_string(), 100)
+            .with_unbounded_pot("savings".to_string())
+            .with_pot("bills".to_string());
+
+        let pots = vec![
+            pot("Bills", 0, Some(5_000)),
+            pot("Savings", 1_000_000, None),
+        ];
+
+        assert_eq!(
+            transactions(100_000, pots, &sweep),
+            Err(Error::UnboundedNotLast("Savings".to_string()))
+        );
+    }
+
+    #[test]
+    fn unlisted_pots_without_goals_are_ignored() {
+        let sweep = Sweep::new(ACCOUNT_ID.to_string(), 0).with_pot("bills".to_string());
+
+        let pots = vec![pot("Bills", 0, Some(5_000)), pot("Holiday", 0, None)];
+
+        assert_eq!(
+            transactions(1_000, pots, &sweep),
+            Ok(vec![("Bills".to_string(), 1_000)])
+        );
+    }
+
+    #[test]
+    fn listed_pot_without_goal() {
+        let sweep = Sweep::new(ACCOUNT_ID.to_string(), 0).with_pot("holiday".to_string());
+
+        assert_eq!(
+            transactions(1_000, vec![pot("Holiday", 0, None)], &sweep),
+            Err(Error::NoPotGoal("Holiday".to_string()))
+        );
+    }
+
+    #[test]
+    fn pots_needing_goals() {
+        let sweep = Sweep::new(ACCOUNT_ID.to_string(), 100)
+            .with_pot("bills".to_string())
+            .with_pot_target("allowance".to_string(), 50)
+            .with_unbounded_pot("savings".to_string());
+
+        let needs_goal: Vec<_> = sweep
+            .pots()
+            .iter()
+            .map(|pot| (pot.name, pot.needs_goal))
+            .collect();
+        assert_eq!(
+            needs_goal,
+            vec![("bills", true), ("allowance", false), ("savings", false)]
+        );
+    }
+
+    #[test]
+    fn currency_mismatch() {
+        let sweep = Sweep::new(ACCOUNT_ID.to_string(), "€100".parse::<Amount>().unwrap())
+            .with_pot("bills".to_string());
+
+        assert!(matches!(
+            transactions(20_000, vec![pot("Bills", 0, Some(5_000))], &sweep),
+            Err(Error::Money(crate::money::Error::CurrencyMismatch { .. }))
+        ));
     }
 }
@@ -0,0 +1,295 @@
+use monzo::Pot;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::find_pot;
+use crate::{ledger::Ledger, money::Amount, operation::Operation, validate::PotRef, State};
+
+/// Errors that can occur when processing a [`TopUp`] operation
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum Error {
+    /// a [`NotFound`](Self::NotFound) is returned when an account or pot
+    /// configured in the [`TopUp`] operation cannot be found in the monzo
+    /// [`State`]
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    /// A configured amount couldn't be used with the account
+    #[error(transparent)]
+    Money(#[from] crate::money::Error),
+
+    /// The operation generated a transaction that couldn't be added to the
+    /// ledger
+    #[error(transparent)]
+    Ledger(#[from] crate::ledger::Error),
+}
+
+/// A [`TopUp`] operation refills the current account from a list of buffer
+/// pots when its balance drops below a floor.
+///
+/// The pots are drained in order, down to their respective minimum balances,
+/// until the current account is back at its goal amount. It is the inverse of
+/// a [`Sweep`](super::Sweep).
+///
+/// # Example
+///
+/// ```
+/// use monz0_lib::operation::TopUp;
+///
+/// let top_up = TopUp::new("ACCOUNT_ID".into(), 50)
+///     .with_account_goal(100)
+///     .with_pot("buffer".into(), 0)
+///     .with_pot("savings".into(), 1000);
+/// ```
+///
+/// The top-up operation also implements [`serde::Deserialize`], and
+/// [`schemars::JsonSchema`] to describe its config. Amounts are written as
+/// described in [`Amount`].
+///
+/// ```
+/// use monz0_lib::operation::TopUp;
+///
+/// let config = r#"
+/// account_floor: £50
+/// account_goal: £100
+///
+/// pots:
+///   - name: buffer
+///   - name: savings
+///     minimum: 1000 GBP
+/// "#;
+///
+/// let top_up: TopUp = serde_yaml::from_str(config).unwrap();
+/// ```
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TopUp {
+    /// The ID of the account to be topped up
+    #[serde(default)]
+    account_id: String,
+
+    /// The current account is only topped up when its balance drops below this
+    /// amount
+    account_floor: Amount,
+
+    /// The amount the current account is topped up to. Defaults to the
+    /// account floor.
+    #[serde(default)]
+    account_goal: Option<Amount>,
+
+    /// The pots to withdraw from, in order
+    pots: Vec<BufferPot>,
+}
+
+/// A pot that a [`TopUp`] operation may withdraw from
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct BufferPot {
+    /// The name of the pot
+    ///
+    /// When determining the pots, the names are normalised by removing emojis,
+    /// normalising capitalisation, and then stripping any leading or trailing
+    /// whitespace.
+    name: String,
+
+    /// The pot is never drawn down below this amount
+    #[serde(default)]
+    minimum: Amount,
+}
+
+impl TopUp {
+    /// Create a new [`TopUp`] operation
+    #[must_use]
+    pub fn new(account_id: String, account_floor: impl Into<Amount>) -> Self {
+        Self {
+            account_id,
+            account_floor: account_floor.into(),
+            account_goal: None,
+            pots: Vec::default(),
+        }
+    }
+
+    /// Set the amount the current account is topped up to.
+    ///
+    /// If not set, the account is topped up to the account floor.
+    #[must_use]
+    pub fn with_account_goal(mut self, account_goal: impl Into<Amount>) -> Self {
+        self.account_goal = Some(account_goal.into());
+        self
+    }
+
+    /// Add a pot to withdraw from, along with the minimum balance that the pot
+    /// should be left with.
+    ///
+    /// Pot names are normalised before comparison, by removing non-ASCII
+    /// characters, capitalisation, and leading/trailing whitespace.
+    #[must_use]
+    pub fn with_pot(mut self, name: String, minimum: impl Into<Amount>) -> Self {
+        self.pots.push(BufferPot {
+            name,
+            minimum: minimum.into(),
+        });
+        self
+    }
+
+    /// The account floor and goal, in minor units of the given currency
+    fn account_limits(&self, currency: &str) -> Result<(i64, i64), Error> {
+        let floor = self.account_floor.minor_units(currency)?;
+        let goal = match self.account_goal {
+            Some(goal) => goal.minor_units(currency)?.max(floor),
+            None => floor,
+        };
+        Ok((floor, goal))
+    }
+}
+
+impl Operation for TopUp {
+    type Err = Error;
+
+    const NAME: &'static str = "TopUp";
+
+    fn account_ids(&self) -> Vec<&str> {
+        vec![&self.account_id]
+    }
+
+    fn pots(&self) -> Vec<PotRef<'_>> {
+        self.pots
+            .iter()
+            .map(|pot| PotRef {
+                account_id: &self.account_id,
+                name: &pot.name,
+                needs_goal: false,
+            })
+            .collect()
+    }
+
+    fn transactions<'a>(&'a self, state: &'a State) -> Result<Ledger<'a>, Self::Err> {
+        let account_state = state
+            .get(&self.account_id)
+            .ok_or_else(|| Error::NotFound(format!("account {} not found", self.account_id)))?;
+        let balance = account_state.balance.balance;
+        let (account_floor, account_goal) = self.account_limits(&account_state.balance.currency)?;
+
+        let pots = self
+            .pots
+            .iter()
+            .map(|buffer| {
+                let pot = find_pot(&account_state.pots, &self.account_id, &buffer.name)
+                    .ok_or_else(|| {
+                        Error::NotFound(format!("failed to find pot: {}", buffer.name))
+                    })?;
+                Ok((pot, buffer.minimum.minor_units(&pot.currency)?))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut ledger = Ledger::default();
+
+        if balance >= account_floor {
+            return Ok(ledger);
+        }
+
+        for (pot, amount) in calculate_withdrawals(account_goal - balance, pots) {
+            ledger.push(&self.account_id, pot, -amount)?;
+        }
+
+        Ok(ledger)
+    }
+}
+
+/// Withdraw up to `shortfall` from the pots in order, leaving each pot with at
+/// least its minimum balance.
+fn calculate_withdrawals(mut shortfall: i64, pots: Vec<(&Pot, i64)>) -> Vec<(&Pot, i64)> {
+    let mut withdrawals = Vec::default();
+
+    for (pot, minimum) in pots {
+        if shortfall <= 0 {
+            break;
+        }
+
+        let available = (pot.balance - minimum).max(0);
+        let withdrawal = available.min(shortfall);
+
+        shortfall -= withdrawal;
+        withdrawals.push((pot, withdrawal));
+    }
+
+    withdrawals
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::test_support::{pot, state, ACCOUNT_ID};
+
+    #[test]
+    fn deserialise_yaml() {
+        let raw = "
+        account_floor: 50
+        account_goal: 100
+
+        pots:
+          - name: buffer
+          - name: savings
+            minimum: 1000
+        ";
+
+        serde_yaml::from_str::<TopUp>(raw).unwrap();
+    }
+
+    fn withdrawals(balance: i64, pots: Vec<Pot>, top_up: &TopUp) -> Vec<(String, u32)> {
+        let state = state(balance, pots);
+        let ledger = top_up.transactions(&state).unwrap();
+
+        let mut withdrawals = Vec::default();
+        for (_account_id, transactions) in &ledger {
+            assert!(transactions.deposits.is_empty());
+            withdrawals.extend(
+                transactions
+                    .withdrawals
+                    .iter()
+                    .map(|(pot, amount)| (pot.name.clone(), *amount)),
+            );
+        }
+        withdrawals
+    }
+
+    #[test_case(6_000 => Vec::<(String, u32)>::new(); "above floor")]
+    #[test_case(5_000 => Vec::<(String, u32)>::new(); "at floor")]
+    #[test_case(4_000 => vec![("Buffer".to_string(), 2_000), ("Savings".to_string(), 4_000)]; "below floor")]
+    #[test_case(0 => vec![("Buffer".to_string(), 2_000), ("Savings".to_string(), 5_000)]; "insufficient buffer")]
+    fn top_up(balance: i64) -> Vec<(String, u32)> {
+        let top_up = TopUp::new(ACCOUNT_ID.to_string(), 50)
+            .with_account_goal(100)
+            .with_pot("buffer".to_string(), 0)
+            .with_pot("savings".to_string(), 10);
+
+        withdrawals(
+            balance,
+            vec![pot("Buffer", 2_000, None), pot("Savings", 6_000, None)],
+            &top_up,
+        )
+    }
+
+    #[test]
+    fn default_goal_is_floor() {
+        let top_up = TopUp::new(ACCOUNT_ID.to_string(), 50).with_pot("buffer".to_string(), 0);
+
+        assert_eq!(
+            withdrawals(4_000, vec![pot("Buffer", 2_000, None)], &top_up),
+            vec![("Buffer".to_string(), 1_000)]
+        );
+    }
+
+    #[test]
+    fn missing_pot() {
+        let state = state(0, vec![pot("Buffer", 2_000, None)]);
+        let top_up = TopUp::new(ACCOUNT_ID.to_string(), 50).with_pot("savings".to_string(), 0);
+
+        assert_eq!(
+            top_up.transactions(&state).unwrap_err(),
+            Error::NotFound("failed to find pot: savings".to_string())
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Reports describing the outcome of processing an
+//! [`OwnedLedger`](crate::OwnedLedger)
+
+use std::fmt;
+
+use rusty_money::{iso, Money};
+
+use crate::{ledger::Transfer, Error};
+
+/// The direction of a [`Transfer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// A withdrawal from a pot into the current account
+    Withdrawal,
+
+    /// A deposit from the current account into a pot
+    Deposit,
+}
+
+/// A single [`Transfer`], along with the account it was made from and its
+/// [`Direction`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The ID of the current account
+    pub account_id: String,
+
+    /// Whether the transfer is a withdrawal or a deposit
+    pub direction: Direction,
+
+    /// The transfer itself
+    pub transfer: Transfer,
+}
+
+/// A record of which transfers were completed when processing a ledger.
+///
+/// If a withdrawal fails, the deposits for that account are skipped, since the
+/// current account may no longer have enough cash to cover them. Transfers for
+/// other accounts are unaffected.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Transfers that completed successfully
+    pub completed: Vec<Entry>,
+
+    /// Transfers that were attempted and failed, with their respective errors
+    pub failed: Vec<(Entry, Error)>,
+
+    /// Transfers that were never attempted
+    pub skipped: Vec<Entry>,
+}
+
+impl Report {
+    /// Returns true if every transfer completed successfully
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.skipped.is_empty()
+    }
+
+    /// Merge another [`Report`] into this one
+    pub fn extend(&mut self, other: Self) {
+        self.completed.extend(other.completed);
+        self.failed.extend(other.failed);
+        self.skipped.extend(other.skipped);
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (direction, preposition) = match self.direction {
+            Direction::Withdrawal => ("withdraw", "from"),
+            Direction::Deposit => ("deposit", "into"),
+        };
+
+        write!(f, "{}: {} ", self.account_id, direction)?;
+        match iso::find(&self.transfer.currency) {
+            Some(currency) => write!(
+                f,
+                "{} {}",
+                Money::from_minor(self.transfer.amount.into(), currency).amount(),
+                currency.iso_alpha_code
+            )?,
+            // an unknown currency can't be converted to major units
+            None => write!(
+                f,
+                "{} ({} minor units)",
+                self.transfer.amount, self.transfer.currency
+            )?,
+        }
+        write!(f, " {} '{}'", preposition, self.transfer.pot_name)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "completed:")?;
+        for entry in &self.completed {
+            writeln!(f, "  {}", entry)?;
+        }
+
+        writeln!(f, "failed:")?;
+        for (entry, error) in &self.failed {
+            writeln!(f, "  {} ({})", entry, error)?;
+        }
+
+        writeln!(f, "skipped:")?;
+        for entry in &self.skipped {
+            writeln!(f, "  {}", entry)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Direction, Entry, Report};
+    use crate::{ledger::Transfer, Error};
+
+    fn entry(direction: Direction, pot_name: &str, currency: &str, amount: u32) -> Entry {
+        Entry {
+            account_id: "ACCOUNT_ID".to_string(),
+            direction,
+            transfer: Transfer {
+                pot_id: format!("{}_ID", pot_name),
+                pot_name: pot_name.to_string(),
+                currency: currency.to_string(),
+                amount,
+                dedupe_id: "DEDUPE_ID".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn display() {
+        let report = Report {
+            completed: vec![entry(Direction::Withdrawal, "Holiday", "GBP", 1_050)],
+            failed: vec![(
+                entry(Direction::Deposit, "Bills", "GBP", 500),
+                Error::NotFound("Bills".to_string()),
+            )],
+            skipped: vec![entry(Direction::Deposit, "Travel", "XYZ", 20)],
+        };
+
+        assert_eq!(
+            report.to_string(),
+            concat!(
+                "completed:\n",
+                "  ACCOUNT_ID: withdraw 10.50 GBP from 'Holiday'\n",
+                "failed:\n",
+                "  ACCOUNT_ID: deposit 5.00 GBP into 'Bills' (not found: Bills)\n",
+                "skipped:\n",
+                "  ACCOUNT_ID: deposit 20 (XYZ minor units) into 'Travel'\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Retrying requests which fail with a temporary error
+//!
+//! See [`RetryPolicy`].
+
+use std::{future::Future, time::Duration};
+
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{self, Error};
+
+/// How the [`Client`](crate::Client) retries requests that fail with a
+/// [transient](Error::Transient) or [rate-limiting](Error::RateLimited) error.
+///
+/// The delay before each retry grows exponentially from `base_delay`, up to
+/// `max_delay`. With `jitter`, a random delay between zero and that limit is
+/// used instead, so that concurrent requests don't retry in lockstep.
+///
+/// Pot transfers are retried too, since they are always sent with a dedupe ID
+/// and so can't be applied twice.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use monz0_lib::{backend::InMemory, Client, RetryPolicy, State};
+///
+/// let policy = RetryPolicy {
+///     max_attempts: 5,
+///     base_delay: Duration::from_secs(1),
+///     ..RetryPolicy::default()
+/// };
+///
+/// let client = Client::new(InMemory::new(State::default())).with_retry_policy(policy);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first. A value of `1`
+    /// disables retries.
+    pub max_attempts: u32,
+
+    /// The delay before the first retry
+    #[serde(rename = "base_delay_ms", with = "millis")]
+    #[schemars(with = "u64")]
+    pub base_delay: Duration,
+
+    /// The longest delay between retries
+    #[serde(rename = "max_delay_ms", with = "millis")]
+    #[schemars(with = "u64")]
+    pub max_delay: Duration,
+
+    /// Randomise the delay between retries
+    pub jitter: bool,
+
+    /// Wait at least as long as the Monzo API asks for in a `Retry-After`
+    /// header.
+    ///
+    /// If the requested delay is longer than `max_delay`, the request is not
+    /// retried.
+    pub respect_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+            respect_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy which never retries
+    #[must_use]
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// The longest delay before the given retry (starting from `1`)
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(retry.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// The delay before retrying a request that has failed `attempts` times
+    /// with the given error, or `None` if the request shouldn't be retried
+    fn delay(&self, attempts: u32, error: &Error) -> Option<Duration> {
+        if attempts >= self.max_attempts || !error.is_retryable() {
+            return None;
+        }
+
+        let mut delay = self.backoff(attempts);
+        if self.jitter {
+            delay = rand::thread_rng().gen_range(Duration::ZERO..=delay);
+        }
+
+        if self.respect_retry_after {
+            if let Some(retry_after) = error.retry_after() {
+                if retry_after > self.max_delay {
+                    return None;
+                }
+                delay = delay.max(retry_after);
+            }
+        }
+
+        Some(delay)
+    }
+
+    /// Run a request, retrying it according to the policy
+    pub(crate) async fn run<F, Fut, R>(&self, f: F) -> error::Result<R>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = error::Result<R>>,
+    {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match f().await {
+                Err(error) => error,
+                response => return response,
+            };
+
+            match self.delay(attempts, &error) {
+                Some(delay) => {
+                    tracing::warn!(
+                        "request failed ({}), retrying in {:?} (attempt {} of {})",
+                        error,
+                        delay,
+                        attempts + 1,
+                        self.max_attempts
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                None => return Err(error),
+            }
+        }
+    }
+}
+
+/// (De)serialise a [`Duration`] as a whole number of milliseconds
+mod millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    };
+
+    use test_case::test_case;
+
+    use super::RetryPolicy;
+    use crate::error::{Cause, Error};
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::from_millis(10),
+            jitter: false,
+            respect_retry_after: true,
+        }
+    }
+
+    fn api_error(status: u16, retry_after: Option<Duration>) -> Error {
+        Cause::Api {
+            status,
+            code: String::default(),
+            message: String::default(),
+            retry_after,
+        }
+        .into()
+    }
+
+    #[test_case(1 => Duration::from_millis(100); "first retry")]
+    #[test_case(2 => Duration::from_millis(200); "second retry")]
+    #[test_case(4 => Duration::from_millis(800); "fourth retry")]
+    #[test_case(5 => Duration::from_secs(1); "capped")]
+    #[test_case(100 => Duration::from_secs(1); "overflow")]
+    fn backoff(retry: u32) -> Duration {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            ..policy()
+        };
+
+        policy.backoff(retry)
+    }
+
+    #[test_case(1, 503, None => Some(Duration::ZERO); "transient")]
+    #[test_case(1, 429, None => Some(Duration::ZERO); "rate limited")]
+    #[test_case(1, 400, None => None; "permanent")]
+    #[test_case(1, 401, None => None; "auth expired")]
+    #[test_case(3, 503, None => None; "out of attempts")]
+    #[test_case(1, 429, Some(Duration::from_millis(5)) => Some(Duration::from_millis(5)); "retry after")]
+    #[test_case(1, 429, Some(Duration::from_secs(60)) => None; "retry after too long")]
+    fn delay(attempts: u32, status: u16, retry_after: Option<Duration>) -> Option<Duration> {
+        policy().delay(attempts, &api_error(status, retry_after))
+    }
+
+    #[test]
+    fn jitter() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            jitter: true,
+            ..policy()
+        };
+
+        for _ in 0..100 {
+            let delay = policy.delay(1, &api_error(503, None)).unwrap();
+            assert!(delay <= Duration::from_millis(10));
+        }
+    }
+
+    #[test_case(0 => (true, 1); "success")]
+    #[test_case(2 => (true, 3); "recovers")]
+    #[test_case(3 => (false, 3); "gives up")]
+    #[tokio::test]
+    async fn run(failures: u32) -> (bool, u32) {
+        let attempts = AtomicU32::default();
+
+        let result = policy()
+            .run(|| async {
+                if attempts.fetch_add(1, Ordering::SeqCst) < failures {
+                    Err(api_error(503, None))
+                } else {
+                    Ok(())
+                }
+            })
+            .await;
+
+        (result.is_ok(), attempts.into_inner())
+    }
+
+    #[test]
+    fn deserialise_yaml() {
+        let raw = "
+        max_attempts: 5
+        base_delay_ms: 250
+        ";
+
+        let policy: RetryPolicy = serde_yaml::from_str(raw).unwrap();
+        assert_eq!(
+            policy,
+            RetryPolicy {
+                max_attempts: 5,
+                base_delay: Duration::from_millis(250),
+                ..RetryPolicy::default()
+            }
+        );
+    }
+}
@@ -3,12 +3,18 @@
 use std::collections::HashMap;
 
 use monzo::{Balance, Pot};
+use serde::Deserialize;
+
+use crate::ledger::{self, OwnedLedger};
 
 /// A map from account IDs to their respective [`state::Account`](Account)s
 pub type State = HashMap<String, Account>;
 
-/// The balance and pots associated with an account
-#[derive(Debug)]
+/// The balance and pots associated with an account.
+///
+/// An account can be deserialised from the Monzo API's representation of the
+/// balance and pots, to work with a saved snapshot.
+#[derive(Debug, Deserialize)]
 pub struct Account {
     /// the current balance of the account
     pub balance: Balance,
@@ -16,3 +22,134 @@ pub struct Account {
     /// the pots associated with an account
     pub pots: Vec<Pot>,
 }
+
+impl Clone for Account {
+    fn clone(&self) -> Self {
+        Self {
+            balance: copy_balance(&self.balance),
+            pots: self.pots.clone(),
+        }
+    }
+}
+
+/// Project the [`State`] that would result from processing the given
+/// [`OwnedLedger`], leaving the original [`State`] untouched.
+///
+/// Each transfer moves money between the `balance` of the account and the
+/// `balance` of the pot. The `total_balance` of the account includes its pots,
+/// so is unchanged.
+///
+/// This allows the effects of an operation to be previewed, or several
+/// operations to be chained together, without talking to Monzo. It also allows
+/// a single snapshot of the [`State`] to be reused after each ledger is
+/// processed, rather than fetching it again.
+///
+/// # Example
+///
+/// ```no_run
+/// use monz0_lib::{operation::Sweep, state, Client, Operation, OwnedLedger};
+///
+/// # async fn example(client: Client) -> Result<(), Box<dyn std::error::Error>> {
+/// let current = client.state().await?;
+///
+/// let sweep = Sweep::new("ACCOUNT_ID".into(), 100).with_pot("bills".into());
+/// let ledger = OwnedLedger::from(sweep.transactions(&current)?);
+///
+/// let projected = state::project(&current, &ledger)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if an account or pot in the ledger doesn't exist in the
+/// [`State`]. The ledger isn't otherwise validated, so pots may be left with
+/// negative balances. See [`OwnedLedger::validate`].
+pub fn project(state: &State, ledger: &OwnedLedger) -> Result<State, ledger::Error> {
+    let mut projected = state.clone();
+
+    for (account_id, transactions) in ledger.iter() {
+        let account = projected
+            .get_mut(account_id)
+            .ok_or_else(|| ledger::Error::NotFound(format!("account {} not found", account_id)))?;
+
+        for (transfer, amount) in transactions.signed() {
+            let pot = account
+                .pots
+                .iter_mut()
+                .find(|pot| pot.id == transfer.pot_id && !pot.deleted)
+                .ok_or_else(|| {
+                    ledger::Error::NotFound(format!("pot {} not found", transfer.pot_id))
+                })?;
+
+            pot.balance += amount;
+            account.balance.balance -= amount;
+        }
+    }
+
+    Ok(projected)
+}
+
+/// [`Balance`] doesn't implement [`Clone`], so it has to be copied field by
+/// field
+pub(crate) fn copy_balance(balance: &Balance) -> Balance {
+    Balance {
+        balance: balance.balance,
+        total_balance: balance.total_balance,
+        currency: balance.currency.clone(),
+        spend_today: balance.spend_today,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::project;
+    use crate::{
+        ledger::Error,
+        operation::Sweep,
+        test_support::{pot, state, ACCOUNT_ID},
+        Ledger, Operation, OwnedLedger,
+    };
+
+    #[test]
+    fn project_ledger() {
+        let current = state(
+            20_000,
+            vec![pot("Bills", 0, Some(5_000)), pot("Savings", 1_000, None)],
+        );
+
+        let sweep = Sweep::new(ACCOUNT_ID.to_string(), 100)
+            .with_pot("bills".to_string())
+            .with_unbounded_pot("savings".to_string());
+        let ledger = OwnedLedger::from(sweep.transactions(&current).unwrap());
+
+        let projected = project(&current, &ledger).unwrap();
+
+        let account = &projected[ACCOUNT_ID];
+        let balances: Vec<_> = account.pots.iter().map(|pot| pot.balance).collect();
+        assert_eq!(account.balance.balance, 10_000);
+        assert_eq!(account.balance.total_balance, 20_000);
+        assert_eq!(balances, vec![5_000, 6_000]);
+
+        // the original state is unchanged
+        assert_eq!(current[ACCOUNT_ID].balance.balance, 20_000);
+        assert_eq!(current[ACCOUNT_ID].pots[0].balance, 0);
+
+        // a second sweep of the projected state has nothing to do
+        assert!(sweep.transactions(&projected).unwrap().is_empty());
+    }
+
+    #[test]
+    fn project_missing_pot() {
+        let missing = pot("Holiday", 0, None);
+        let current = state(1_000, vec![pot("Savings", 500, None)]);
+
+        let mut ledger = Ledger::default();
+        ledger.push(ACCOUNT_ID, &missing, 100).unwrap();
+
+        assert_eq!(
+            project(&current, &OwnedLedger::from(ledger)).unwrap_err(),
+            Error::NotFound("pot pot_Holiday not found".to_string())
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! Helpers for constructing test fixtures
+
+use monzo::{Balance, Pot};
+
+use crate::{state::Account, State};
+
+pub const ACCOUNT_ID: &str = "acc_1234";
+
+pub fn pot(name: &str, balance: i64, goal_amount: Option<i64>) -> Pot {
+    let goal_amount = goal_amount.map_or_else(|| "null".to_string(), |goal| goal.to_string());
+    let pot = format!(
+        r#"
+    {{
+        "id": "pot_{name}",
+        "name": "{name}",
+        "style": "teal",
+        "balance": {balance},
+        "currency": "GBP",
+        "goal_amount": {goal_amount},
+        "type": "flexible_savings",
+        "product_id": "XXX",
+        "current_account_id": "{ACCOUNT_ID}",
+        "cover_image_url": "",
+        "isa_wrapper": "ISA",
+        "round_up": false,
+        "round_up_multiplier": null,
+        "is_tax_pot": false,
+        "created": "2019-04-28T06:36:54.318Z",
+        "updated": "2019-05-11T00:31:04.256Z",
+        "deleted": false,
+        "locked": false,
+        "charity_id": "",
+        "available_for_bills": false
+    }}
+    "#
+    );
+
+    serde_yaml::from_str(&pot).unwrap()
+}
+
+pub fn balance(balance: i64) -> Balance {
+    let raw = format!(
+        r#"
+    {{
+        "balance": {balance},
+        "total_balance": {balance},
+        "balance_including_flexible_savings": {balance},
+        "currency": "GBP",
+        "spend_today": 0,
+        "local_currency": "",
+        "local_exchange_rate": 0,
+        "local_spend": []
+    }}
+    "#
+    );
+
+    serde_yaml::from_str(&raw).unwrap()
+}
+
+pub fn state(balance: i64, pots: Vec<Pot>) -> State {
+    let mut state = State::default();
+    state.insert(
+        ACCOUNT_ID.to_string(),
+        Account {
+            balance: self::balance(balance),
+            pots,
+        },
+    );
+    state
+}
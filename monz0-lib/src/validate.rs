@@ -0,0 +1,212 @@
+//! Checking configured operations for mistakes, without generating any
+//! transactions
+//!
+//! Each [`Operation`](crate::Operation) describes the [pots it uses](PotRef).
+//! These can be checked against each other with [`conflicts`], and against the
+//! accounts [`State`] with [`check`].
+
+use std::collections::HashMap;
+
+use crate::{operation::normalise, State};
+
+/// A pot used by an [`Operation`](crate::Operation), as configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PotRef<'a> {
+    /// The ID of the account the pot belongs to
+    pub account_id: &'a str,
+
+    /// The configured name of the pot
+    pub name: &'a str,
+
+    /// Whether the operation relies on the goal amount set in Monzo
+    pub needs_goal: bool,
+}
+
+/// A problem with a configured operation
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum Problem {
+    /// The account doesn't exist
+    #[error("account {0} not found")]
+    AccountNotFound(String),
+
+    /// No pot with this name exists in the account
+    #[error("pot '{0}' not found")]
+    PotNotFound(String),
+
+    /// The only pot with this name has been deleted
+    #[error("pot '{0}' has been deleted")]
+    PotDeleted(String),
+
+    /// The operation relies on the goal amount of the pot, but it isn't set
+    #[error("pot '{0}' has no 'goal amount' set")]
+    NoPotGoal(String),
+
+    /// The pot is also used by an earlier operation, given by its index
+    #[error("pot '{name}' is also used by operation {}", .operation + 1)]
+    Claimed {
+        /// The configured name of the pot
+        name: String,
+
+        /// The index of the earlier operation
+        operation: usize,
+    },
+}
+
+/// Find the pots which are used by more than one operation.
+///
+/// `operations` holds the pots used by each operation, in order. The problems
+/// are returned along with the index of the operation they were found in.
+#[must_use]
+pub fn conflicts(operations: &[Vec<PotRef<'_>>]) -> Vec<(usize, Problem)> {
+    let mut claimed = HashMap::new();
+    let mut problems = Vec::default();
+
+    for (index, pots) in operations.iter().enumerate() {
+        for pot in pots {
+            let key = (pot.account_id, normalise(pot.name));
+
+            match claimed.get(&key) {
+                Some(&operation) if operation != index => problems.push((
+                    index,
+                    Problem::Claimed {
+                        name: pot.name.to_string(),
+                        operation,
+                    },
+                )),
+                Some(_) => (),
+                None => {
+                    claimed.insert(key, index);
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+/// Check that the pots used by each operation exist in the [`State`], haven't
+/// been deleted, and have a goal amount set if the operation needs one.
+///
+/// `operations` holds the pots used by each operation, in order. The problems
+/// are returned along with the index of the operation they were found in.
+///
+/// # Example
+///
+/// ```
+/// use monz0_lib::{operation::Sweep, validate, Operation, State};
+///
+/// let sweep = Sweep::new("ACCOUNT_ID".into(), 100).with_pot("bills".into());
+///
+/// let problems = validate::check(&[sweep.pots()], &State::default());
+/// assert_eq!(
+///     problems[0].1,
+///     validate::Problem::AccountNotFound("ACCOUNT_ID".to_string())
+/// );
+/// ```
+#[must_use]
+pub fn check(operations: &[Vec<PotRef<'_>>], state: &State) -> Vec<(usize, Problem)> {
+    let mut problems = Vec::default();
+
+    for (index, pots) in operations.iter().enumerate() {
+        for pot in pots {
+            if let Some(problem) = check_pot(pot, state) {
+                problems.push((index, problem));
+            }
+        }
+    }
+
+    problems.dedup();
+    problems
+}
+
+fn check_pot(pot: &PotRef<'_>, state: &State) -> Option<Problem> {
+    let account = match state.get(pot.account_id) {
+        Some(account) => account,
+        None => return Some(Problem::AccountNotFound(pot.account_id.to_string())),
+    };
+
+    let name = normalise(pot.name);
+    let (active, deleted): (Vec<_>, Vec<_>) = account
+        .pots
+        .iter()
+        .filter(|candidate| candidate.current_account_id == pot.account_id)
+        .filter(|candidate| normalise(&candidate.name) == name)
+        .partition(|candidate| !candidate.deleted);
+
+    match active.first() {
+        Some(found) if pot.needs_goal && found.goal_amount.is_none() => {
+            Some(Problem::NoPotGoal(pot.name.to_string()))
+        }
+        Some(_) => None,
+        None if !deleted.is_empty() => Some(Problem::PotDeleted(pot.name.to_string())),
+        None => Some(Problem::PotNotFound(pot.name.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::{check, conflicts, PotRef, Problem};
+    use crate::{
+        test_support::{pot, state, ACCOUNT_ID},
+        State,
+    };
+
+    fn pot_ref(name: &str, needs_goal: bool) -> PotRef<'_> {
+        PotRef {
+            account_id: ACCOUNT_ID,
+            name,
+            needs_goal,
+        }
+    }
+
+    #[test_case("bills", true => None; "found")]
+    #[test_case("💡 BILLS ", true => None; "normalised")]
+    #[test_case("holiday", false => Some(Problem::PotNotFound("holiday".to_string())); "not found")]
+    #[test_case("old", false => Some(Problem::PotDeleted("old".to_string())); "deleted")]
+    #[test_case("savings", true => Some(Problem::NoPotGoal("savings".to_string())); "no goal")]
+    #[test_case("savings", false => None; "goal not needed")]
+    fn check_pot(name: &str, needs_goal: bool) -> Option<Problem> {
+        let mut old = pot("Old", 0, Some(100));
+        old.deleted = true;
+        let state = state(
+            1_000,
+            vec![pot("Bills", 0, Some(5_000)), pot("Savings", 0, None), old],
+        );
+
+        check(&[vec![pot_ref(name, needs_goal)]], &state)
+            .pop()
+            .map(|(_, problem)| problem)
+    }
+
+    #[test]
+    fn missing_account() {
+        let operations = vec![vec![pot_ref("bills", false), pot_ref("savings", false)]];
+
+        assert_eq!(
+            check(&operations, &State::default()),
+            vec![(0, Problem::AccountNotFound(ACCOUNT_ID.to_string()))]
+        );
+    }
+
+    #[test]
+    fn claimed_by_two_operations() {
+        let operations = vec![
+            vec![pot_ref("bills", true), pot_ref("savings", false)],
+            vec![pot_ref("holiday", false)],
+            vec![pot_ref("Savings", false)],
+        ];
+
+        assert_eq!(
+            conflicts(&operations),
+            vec![(
+                2,
+                Problem::Claimed {
+                    name: "Savings".to_string(),
+                    operation: 0
+                }
+            )]
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! The YAML fixture describing the initial state of the fake Monzo API
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// The initial state of the fake Monzo API
+///
+/// # Example
+///
+/// ```
+/// use monz0_test_server::Fixture;
+///
+/// let fixture = r#"
+/// auth:
+///   access_token: ACCESS_TOKEN
+///   refresh_token: REFRESH_TOKEN
+///   client_id: CLIENT_ID
+///   client_secret: CLIENT_SECRET
+///   expired: true
+///
+/// accounts:
+///   acc_1234:
+///     balance: 20000
+///     pots:
+///       - id: pot_bills
+///         name: Bills
+///         balance: 0
+///         goal_amount: 5000
+/// "#;
+///
+/// let fixture: Fixture = serde_yaml::from_str(fixture).unwrap();
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixture {
+    /// The credentials accepted by the server
+    pub auth: Auth,
+
+    /// The accounts, keyed by account ID
+    #[serde(default)]
+    pub accounts: BTreeMap<String, Account>,
+}
+
+impl Fixture {
+    /// Load a [`Fixture`] from a YAML string
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the YAML is invalid
+    pub fn from_yaml(yaml: &str) -> Result<Self, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
+}
+
+/// The OAuth credentials accepted by the server
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Auth {
+    /// The currently valid access token
+    pub access_token: String,
+
+    /// The currently valid refresh token
+    pub refresh_token: String,
+
+    /// The OAuth client ID
+    pub client_id: String,
+
+    /// The OAuth client secret
+    pub client_secret: String,
+
+    /// An authorisation code which can be exchanged (once) for the access and
+    /// refresh tokens, as if the user had just logged in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorisation_code: Option<String>,
+
+    /// Whether the access token has expired. While the token is expired, the
+    /// server responds to every request with '401 Unauthorized' until the
+    /// token is refreshed.
+    #[serde(default)]
+    pub expired: bool,
+
+    /// Whether the user has yet to approve access in the Monzo app. Until
+    /// then, the server responds to every request with '403 Forbidden'.
+    #[serde(default)]
+    pub awaiting_approval: bool,
+}
+
+/// A current account
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Account {
+    /// The balance of the account, in minor units
+    pub balance: i64,
+
+    /// The ISO currency code of the account
+    #[serde(default = "default_currency")]
+    pub currency: String,
+
+    /// The pots associated with the account
+    #[serde(default)]
+    pub pots: Vec<Pot>,
+}
+
+/// A pot
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pot {
+    /// The pot ID
+    pub id: String,
+
+    /// The name of the pot
+    pub name: String,
+
+    /// The balance of the pot, in minor units
+    pub balance: i64,
+
+    /// The goal amount of the pot, in minor units
+    #[serde(default)]
+    pub goal_amount: Option<i64>,
+
+    /// Whether the pot has been deleted
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+fn default_currency() -> String {
+    "GBP".to_string()
+}
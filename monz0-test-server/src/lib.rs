@@ -0,0 +1,135 @@
+//! A fake Monzo API server for end-to-end testing of `monz0`
+//!
+//! The server speaks the subset of the Monzo API used by the `monz0-lib`
+//! client (accounts, balances, pots, pot deposits and withdrawals, and OAuth
+//! token refresh), backed by an in-memory [`Fixture`].
+
+#![deny(
+    clippy::all,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    missing_docs
+)]
+#![warn(clippy::pedantic)]
+
+use std::{
+    collections::HashSet,
+    net::{SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+};
+
+use tokio::sync::oneshot;
+
+pub mod fixture;
+#[doc(inline)]
+pub use fixture::Fixture;
+mod routes;
+
+/// A running fake Monzo API server.
+///
+/// The server runs on a background thread, and is shut down when dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use monz0_test_server::{Fixture, Server};
+///
+/// let fixture = Fixture::from_yaml(include_str!("../fixtures/basic.yml")).unwrap();
+/// let server = Server::start(fixture);
+///
+/// println!("listening on {}", server.url());
+/// ```
+#[derive(Debug)]
+pub struct Server {
+    addr: SocketAddr,
+    state: routes::Shared,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    /// Start a new server on a random local port
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the server cannot bind to a local port
+    #[must_use]
+    pub fn start(fixture: Fixture) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind to local port");
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let state = Arc::new(Mutex::new(routes::State {
+            fixture,
+            refreshes: 0,
+            dedupe_ids: HashSet::default(),
+        }));
+
+        let (shutdown, rx) = oneshot::channel();
+        let app = routes::router(Arc::clone(&state));
+
+        let thread = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            runtime.block_on(async move {
+                axum::Server::from_tcp(listener)
+                    .unwrap()
+                    .serve(app.into_make_service())
+                    .with_graceful_shutdown(async {
+                        rx.await.ok();
+                    })
+                    .await
+                    .unwrap();
+            });
+        });
+
+        Self {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        }
+    }
+
+    /// The base URL of the server
+    #[must_use]
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// A snapshot of the current state of the server
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the internal lock has been poisoned
+    #[must_use]
+    pub fn fixture(&self) -> Fixture {
+        self.state.lock().unwrap().fixture.clone()
+    }
+
+    /// The number of times the access token has been refreshed
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the internal lock has been poisoned
+    #[must_use]
+    pub fn refreshes(&self) -> usize {
+        self.state.lock().unwrap().refreshes
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
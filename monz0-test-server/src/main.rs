@@ -0,0 +1,23 @@
+//! Run the fake Monzo API server from a YAML fixture file
+//!
+//! ```sh
+//! cargo run -p monz0-test-server -- monz0-test-server/fixtures/basic.yml
+//! ```
+
+use monz0_test_server::{Fixture, Server};
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .expect("usage: monz0-test-server <FIXTURE>");
+    let yaml = std::fs::read_to_string(path).expect("failed to read fixture");
+    let fixture = Fixture::from_yaml(&yaml).expect("invalid fixture");
+
+    let server = Server::start(fixture);
+    println!("listening on {}", server.url());
+
+    // run until the process is killed
+    loop {
+        std::thread::park();
+    }
+}
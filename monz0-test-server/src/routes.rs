@@ -0,0 +1,377 @@
+//! Request handlers for the subset of the Monzo API used by `monz0`
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Extension, Form, Path, Query},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{fixture, Fixture};
+
+/// The mutable state of the server
+#[derive(Debug)]
+pub struct State {
+    pub fixture: Fixture,
+    pub refreshes: usize,
+
+    /// The dedupe IDs of the transfers that have been applied
+    pub dedupe_ids: HashSet<String>,
+}
+
+pub type Shared = Arc<Mutex<State>>;
+
+type Response = Result<Json<Value>, (StatusCode, Json<Value>)>;
+
+pub fn router(state: Shared) -> Router {
+    Router::new()
+        .route("/accounts", get(accounts))
+        .route("/balance", get(balance))
+        .route("/pots", get(pots))
+        .route("/pots/:pot_id/deposit", put(deposit))
+        .route("/pots/:pot_id/withdraw", put(withdraw))
+        .route("/oauth2/token", post(token))
+        .layer(Extension(state))
+}
+
+fn error(status: StatusCode, code: &str, message: &str) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({ "code": code, "message": message })))
+}
+
+fn not_found(message: &str) -> (StatusCode, Json<Value>) {
+    error(StatusCode::NOT_FOUND, "not_found", message)
+}
+
+/// Check the bearer token of a request
+fn authorise(state: &State, headers: &HeaderMap) -> Result<(), (StatusCode, Json<Value>)> {
+    let expected = format!("Bearer {}", state.fixture.auth.access_token);
+    let provided = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+
+    if provided != Some(expected.as_str()) {
+        return Err(error(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized.bad_access_token",
+            "invalid access token",
+        ));
+    }
+
+    if state.fixture.auth.expired {
+        return Err(error(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized.bad_access_token.expired",
+            "access token has expired",
+        ));
+    }
+
+    if state.fixture.auth.awaiting_approval {
+        return Err(error(
+            StatusCode::FORBIDDEN,
+            "forbidden.insufficient_permissions",
+            "access has not been approved in the Monzo app",
+        ));
+    }
+
+    Ok(())
+}
+
+fn account_json(id: &str, account: &fixture::Account) -> Value {
+    json!({
+        "id": id,
+        "closed": false,
+        "created": "2019-04-28T06:36:54.318Z",
+        "description": "user_0000",
+        "type": "uk_retail",
+        "owner_type": "personal",
+        "is_flex": false,
+        "product_type": "standard",
+        "currency": account.currency,
+        "legal_entity": "monzo_uk",
+        "country_code": "GB",
+        "country_code_alpha3": "GBR",
+        "owners": [],
+        "account_number": "12345678",
+        "sort_code": "040004",
+    })
+}
+
+fn pot_json(account_id: &str, currency: &str, pot: &fixture::Pot) -> Value {
+    json!({
+        "id": pot.id,
+        "name": pot.name,
+        "style": "teal",
+        "balance": pot.balance,
+        "currency": currency,
+        "goal_amount": pot.goal_amount,
+        "type": "flexible_savings",
+        "product_id": "default",
+        "current_account_id": account_id,
+        "cover_image_url": "",
+        "isa_wrapper": "",
+        "round_up": false,
+        "round_up_multiplier": null,
+        "is_tax_pot": false,
+        "created": "2019-04-28T06:36:54.318Z",
+        "updated": "2019-05-11T00:31:04.256Z",
+        "deleted": pot.deleted,
+        "locked": false,
+        "charity_id": "",
+        "available_for_bills": false,
+    })
+}
+
+async fn accounts(Extension(state): Extension<Shared>, headers: HeaderMap) -> Response {
+    let state = state.lock().unwrap();
+    authorise(&state, &headers)?;
+
+    let accounts: Vec<_> = state
+        .fixture
+        .accounts
+        .iter()
+        .map(|(id, account)| account_json(id, account))
+        .collect();
+
+    Ok(Json(json!({ "accounts": accounts })))
+}
+
+#[derive(Debug, Deserialize)]
+struct BalanceQuery {
+    account_id: String,
+}
+
+async fn balance(
+    Extension(state): Extension<Shared>,
+    headers: HeaderMap,
+    Query(query): Query<BalanceQuery>,
+) -> Response {
+    let state = state.lock().unwrap();
+    authorise(&state, &headers)?;
+
+    let account = state
+        .fixture
+        .accounts
+        .get(&query.account_id)
+        .ok_or_else(|| not_found("account not found"))?;
+
+    let savings: i64 = account.pots.iter().map(|pot| pot.balance).sum();
+
+    Ok(Json(json!({
+        "balance": account.balance,
+        "total_balance": account.balance,
+        "balance_including_flexible_savings": account.balance + savings,
+        "currency": account.currency,
+        "spend_today": 0,
+        "local_currency": "",
+        "local_exchange_rate": 0,
+        "local_spend": [],
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+struct PotsQuery {
+    current_account_id: String,
+}
+
+async fn pots(
+    Extension(state): Extension<Shared>,
+    headers: HeaderMap,
+    Query(query): Query<PotsQuery>,
+) -> Response {
+    let state = state.lock().unwrap();
+    authorise(&state, &headers)?;
+
+    let account = state
+        .fixture
+        .accounts
+        .get(&query.current_account_id)
+        .ok_or_else(|| not_found("account not found"))?;
+
+    let pots: Vec<_> = account
+        .pots
+        .iter()
+        .map(|pot| pot_json(&query.current_account_id, &account.currency, pot))
+        .collect();
+
+    Ok(Json(json!({ "pots": pots })))
+}
+
+#[derive(Debug, Deserialize)]
+struct DepositForm {
+    source_account_id: String,
+    amount: i64,
+    dedupe_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WithdrawForm {
+    destination_account_id: String,
+    amount: i64,
+    dedupe_id: String,
+}
+
+/// Move `amount` from the account into the pot (or out of it, if negative).
+///
+/// Transfers with a dedupe ID that has already been used are ignored.
+fn transfer(
+    state: &mut State,
+    pot_id: &str,
+    account_id: &str,
+    amount: i64,
+    dedupe_id: &str,
+) -> Response {
+    let duplicate = state.dedupe_ids.contains(dedupe_id);
+
+    let account = state
+        .fixture
+        .accounts
+        .get_mut(account_id)
+        .ok_or_else(|| not_found("account not found"))?;
+
+    let pot = account
+        .pots
+        .iter_mut()
+        .find(|pot| pot.id == pot_id && !pot.deleted)
+        .ok_or_else(|| not_found("pot not found"))?;
+
+    if duplicate {
+        return Ok(Json(pot_json(account_id, &account.currency, pot)));
+    }
+
+    if amount > account.balance || -amount > pot.balance {
+        return Err(error(
+            StatusCode::BAD_REQUEST,
+            "bad_request.insufficient_funds",
+            "insufficient funds",
+        ));
+    }
+
+    account.balance -= amount;
+    pot.balance += amount;
+    let response = pot_json(account_id, &account.currency, pot);
+
+    state.dedupe_ids.insert(dedupe_id.to_string());
+
+    Ok(Json(response))
+}
+
+async fn deposit(
+    Extension(state): Extension<Shared>,
+    Path(pot_id): Path<String>,
+    Form(form): Form<DepositForm>,
+    // extracted last, since the 'Form' extractor needs to read the headers
+    headers: HeaderMap,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    authorise(&state, &headers)?;
+
+    transfer(
+        &mut state,
+        &pot_id,
+        &form.source_account_id,
+        form.amount,
+        &form.dedupe_id,
+    )
+}
+
+async fn withdraw(
+    Extension(state): Extension<Shared>,
+    Path(pot_id): Path<String>,
+    Form(form): Form<WithdrawForm>,
+    // extracted last, since the 'Form' extractor needs to read the headers
+    headers: HeaderMap,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    authorise(&state, &headers)?;
+
+    transfer(
+        &mut state,
+        &pot_id,
+        &form.destination_account_id,
+        -form.amount,
+        &form.dedupe_id,
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenForm {
+    grant_type: String,
+    client_id: String,
+    client_secret: String,
+    refresh_token: Option<String>,
+    code: Option<String>,
+}
+
+/// Issue tokens, either by exchanging an authorisation code or by refreshing
+async fn token(Extension(state): Extension<Shared>, Form(form): Form<TokenForm>) -> Response {
+    let mut state = state.lock().unwrap();
+    let auth = &mut state.fixture.auth;
+
+    if form.client_id != auth.client_id || form.client_secret != auth.client_secret {
+        return Err(error(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized.bad_client",
+            "invalid client credentials",
+        ));
+    }
+
+    match form.grant_type.as_str() {
+        "authorization_code" => {
+            if form.code.is_none() || form.code != auth.authorisation_code {
+                return Err(error(
+                    StatusCode::UNAUTHORIZED,
+                    "unauthorized.bad_authorization_code",
+                    "invalid authorisation code",
+                ));
+            }
+
+            // authorisation codes can only be used once
+            auth.authorisation_code = None;
+            Ok(tokens(auth))
+        }
+        "refresh_token" => refresh(&mut state, form.refresh_token.as_deref()),
+        _ => Err(error(
+            StatusCode::BAD_REQUEST,
+            "bad_request.unsupported_grant_type",
+            "unsupported grant type",
+        )),
+    }
+}
+
+fn refresh(state: &mut State, refresh_token: Option<&str>) -> Response {
+    if refresh_token != Some(state.fixture.auth.refresh_token.as_str()) {
+        return Err(error(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized.bad_refresh_token",
+            "invalid refresh token",
+        ));
+    }
+
+    state.refreshes += 1;
+
+    let refreshes = state.refreshes;
+    let auth = &mut state.fixture.auth;
+    auth.access_token = format!("ACCESS_TOKEN_{}", refreshes);
+    auth.refresh_token = format!("REFRESH_TOKEN_{}", refreshes);
+    auth.expired = false;
+
+    Ok(tokens(auth))
+}
+
+fn tokens(auth: &fixture::Auth) -> Json<Value> {
+    Json(json!({
+        "access_token": auth.access_token,
+        "client_id": auth.client_id,
+        "expires_in": 21600,
+        "refresh_token": auth.refresh_token,
+        "token_type": "Bearer",
+        "user_id": "user_0000",
+    }))
+}
@@ -5,22 +5,45 @@ mod show;
 mod run;
 use run::Run;
 
-use crate::logging;
+mod plan;
+use plan::Plan;
 
-#[derive(Debug, Parser, Clone, Copy)]
+mod apply;
+use apply::Apply;
+
+mod login;
+use login::Login;
+
+mod validate;
+use validate::Validate;
+
+mod config;
+use config::ConfigCommand;
+
+use crate::{config::Options, logging};
+
+#[derive(Debug, Parser, Clone)]
 pub struct App {
     #[clap(short, long, parse(from_occurrences), global = true)]
     pub verbose: u8,
 
+    #[clap(flatten)]
+    options: Options,
+
     #[clap(subcommand)]
     subcommand: Option<Subcommand>,
 }
 
-#[derive(Debug, Parser, Clone, Copy, Default)]
+#[derive(Debug, Parser, Clone, Default)]
 enum Subcommand {
     #[default]
     Show,
     Run(Run),
+    Plan(Plan),
+    Apply(Apply),
+    Login(Login),
+    Validate(Validate),
+    Config(ConfigCommand),
 }
 
 impl App {
@@ -32,8 +55,13 @@ impl App {
         logging::set_up(self.verbose);
         tracing::info!("logging configured");
         match self.subcommand.unwrap_or_default() {
-            Subcommand::Show => show::run()?,
-            Subcommand::Run(run) => run.run().await?,
+            Subcommand::Show => show::run(&self.options)?,
+            Subcommand::Run(run) => run.run(&self.options).await?,
+            Subcommand::Plan(plan) => plan.run(&self.options).await?,
+            Subcommand::Apply(apply) => apply.run(&self.options).await?,
+            Subcommand::Login(login) => login.run(&self.options).await?,
+            Subcommand::Validate(validate) => validate.run(&self.options).await?,
+            Subcommand::Config(config) => config.run(&self.options)?,
         }
 
         Ok(())
@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use anyhow::bail;
+use clap::Parser;
+use monz0_lib::state;
+use tracing::instrument;
+
+use crate::{
+    config::{self, Options},
+    plan::Plan,
+    summary::transactions_summary,
+};
+
+/// Apply a plan created by the 'plan' subcommand
+///
+/// The plan is only applied if the account balances haven't changed since it
+/// was created.
+#[derive(Debug, Parser, Clone)]
+pub struct Apply {
+    /// The path of the plan to apply
+    #[clap(default_value = "plan.json")]
+    plan: PathBuf,
+}
+
+impl Apply {
+    #[instrument(skip(self))]
+    pub async fn run(self, options: &Options) -> anyhow::Result<()> {
+        let plan = Plan::load(&self.plan)?;
+
+        if plan.is_empty() {
+            println!("nothing to do ...");
+            return Ok(());
+        }
+
+        let client = config::client(options, &config::load(options)?)?;
+
+        let mut state = client.state_of(plan.account_ids()).await?;
+
+        let drift = plan.drift(&state);
+        if !drift.is_empty() {
+            bail!(
+                "the balances have changed since the plan was created:\n{}",
+                drift.join("\n")
+            );
+        }
+
+        for (name, ledger) in plan.ledgers() {
+            println!("Applying {}", name);
+
+            if ledger.is_empty() {
+                println!("nothing to do ...");
+            } else {
+                println!("{}", transactions_summary(ledger));
+                ledger.validate(&state)?;
+                client.process_ledger(ledger).await?;
+
+                // later steps are validated against the updated balances
+                state = state::project(&state, ledger)?;
+            }
+        }
+
+        Ok(())
+    }
+}
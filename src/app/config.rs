@@ -0,0 +1,123 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::Parser;
+use tracing::instrument;
+
+use crate::config::{self, migrate, Config, Options};
+
+/// Manage the config file
+#[derive(Debug, Parser, Clone)]
+pub struct ConfigCommand {
+    #[clap(subcommand)]
+    action: Action,
+}
+
+#[derive(Debug, clap::Subcommand, Clone)]
+enum Action {
+    Migrate(Migrate),
+    Schema(Schema),
+}
+
+impl ConfigCommand {
+    pub fn run(self, options: &Options) -> anyhow::Result<()> {
+        match self.action {
+            Action::Migrate(migrate) => migrate.run(options),
+            Action::Schema(schema) => schema.run(),
+        }
+    }
+}
+
+/// Rewrite the config file in the current format
+///
+/// The original file is kept alongside it, with the version of its format
+/// added to the name, for example 'config.yml.v1.bak'. Comments in the config
+/// file are not carried over to the migrated file.
+#[derive(Debug, Parser, Clone)]
+pub struct Migrate {
+    /// Print the migrated config, rather than rewriting the file
+    #[clap(long)]
+    dry_run: bool,
+}
+
+impl Migrate {
+    #[instrument(skip(self))]
+    pub fn run(self, options: &Options) -> anyhow::Result<()> {
+        let path = options.config_path()?;
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read '{}'", path.display()))?;
+
+        let (version, migrated) = migrate::migrate(migrate::read(&contents)?)
+            .with_context(|| format!("failed to migrate '{}'", path.display()))?;
+
+        if version == migrate::CURRENT_VERSION {
+            println!(
+                "{}: already at version {}",
+                path.display(),
+                migrate::CURRENT_VERSION
+            );
+            return Ok(());
+        }
+
+        let migrated = serde_yaml::to_string(&migrated)?;
+
+        // make sure the migrated config is usable before replacing the original
+        config::parse(&migrated).with_context(|| {
+            format!(
+                "'{}' was migrated from version {}, but is invalid",
+                path.display(),
+                version
+            )
+        })?;
+
+        if self.dry_run {
+            print!("{}", migrated);
+            return Ok(());
+        }
+
+        let backup = backup_path(&path, version);
+        fs::copy(&path, &backup)
+            .with_context(|| format!("failed to back up '{}'", path.display()))?;
+        fs::write(&path, migrated)
+            .with_context(|| format!("failed to write '{}'", path.display()))?;
+
+        println!(
+            "{}: migrated from version {} to {} (the original is in '{}')",
+            path.display(),
+            version,
+            migrate::CURRENT_VERSION,
+            backup.display()
+        );
+        Ok(())
+    }
+}
+
+/// Print a JSON schema of the config file
+///
+/// The schema can be used by YAML language servers to validate and complete
+/// the config file. For example, save it as 'monz0.schema.json' and add this
+/// comment to the top of the config file:
+///
+///     # yaml-language-server: $schema=monz0.schema.json
+///
+/// The schema describes the current version of the config format.
+#[derive(Debug, Parser, Clone, Copy)]
+pub struct Schema;
+
+impl Schema {
+    #[allow(clippy::unused_self)]
+    pub fn run(self) -> anyhow::Result<()> {
+        let schema = schemars::schema_for!(Config);
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        Ok(())
+    }
+}
+
+fn backup_path(path: &Path, version: u64) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{}.bak", version));
+    path.with_file_name(name)
+}
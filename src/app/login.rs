@@ -0,0 +1,278 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use monz0_lib::{error::Cause, oauth::Login as OAuthLogin, Client, Error};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    time,
+};
+use tracing::instrument;
+use url::Url;
+
+use crate::{
+    config::{self, Options},
+    credentials::Source,
+};
+
+/// How long to wait for the redirect from Monzo
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How long to wait for a request on each connection to the local listener.
+/// Browsers may open connections which they never send a request on.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often to check whether the user has approved access in the Monzo app
+const APPROVAL_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long to wait for the user to approve access in the Monzo app
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Log in to Monzo, and save the credentials
+///
+/// The OAuth client must be created in the Monzo developer portal, with a
+/// redirect URL of `http://127.0.0.1:<PORT>/callback`.
+#[derive(Debug, Parser, Clone)]
+pub struct Login {
+    /// The ID of the OAuth client
+    #[clap(long, env = "MONZ0_CLIENT_ID")]
+    client_id: String,
+
+    /// The secret of the OAuth client
+    #[clap(long, env = "MONZ0_CLIENT_SECRET", hide_env_values = true)]
+    client_secret: String,
+
+    /// The local port to listen on for the redirect from Monzo
+    #[clap(long, default_value = "8080")]
+    port: u16,
+}
+
+/// The query parameters of the redirect from Monzo
+struct Callback {
+    code: String,
+    state: String,
+}
+
+impl Login {
+    #[instrument(skip(self))]
+    pub async fn run(self, options: &Options) -> anyhow::Result<()> {
+        let source = Source::persistent(options, config::load(options)?.credentials)?;
+        let store = source.open()?;
+
+        let listener = TcpListener::bind(("127.0.0.1", self.port))
+            .await
+            .with_context(|| format!("failed to listen on port {}", self.port))?;
+        let redirect_uri = format!(
+            "http://127.0.0.1:{}/callback",
+            listener.local_addr()?.port()
+        );
+
+        let login = OAuthLogin::new(
+            self.client_id,
+            self.client_secret,
+            redirect_uri,
+            options.api_url(),
+        );
+
+        println!("Open this URL in your browser to log in to Monzo:");
+        println!("{}", login.authorisation_url());
+
+        let callback = time::timeout(CALLBACK_TIMEOUT, receive_callback(&listener))
+            .await
+            .context("timed out waiting for the login redirect from Monzo")??;
+        if callback.state != login.state() {
+            bail!("the login redirect doesn't match this login (state token mismatch)");
+        }
+
+        let auth = login.complete(&callback.code).await?;
+        store.save(&auth)?;
+        println!("credentials saved to {}", source);
+
+        let client = config::client_with_auth(auth, options.api_url(), store);
+        wait_for_approval(&client, APPROVAL_TIMEOUT).await?;
+        println!("logged in");
+
+        Ok(())
+    }
+}
+
+/// Wait for the redirect from Monzo, and extract the authorisation code
+async fn receive_callback(listener: &TcpListener) -> anyhow::Result<Callback> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+
+        // an idle connection is dropped, rather than blocking the others
+        let target = time::timeout(REQUEST_TIMEOUT, request_target(&mut stream))
+            .await
+            .unwrap_or(Ok(None))?;
+        let target = match target {
+            Some(target) => target,
+            None => continue,
+        };
+        let url = Url::parse("http://127.0.0.1")?.join(&target)?;
+
+        if url.path() != "/callback" {
+            respond(&mut stream, "404 Not Found", "not found").await?;
+            continue;
+        }
+
+        let param = |name| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+
+        if let Some(error) = param("error") {
+            respond(&mut stream, "400 Bad Request", "login failed").await?;
+            bail!("login failed: {}", error);
+        }
+
+        if let (Some(code), Some(state)) = (param("code"), param("state")) {
+            respond(
+                &mut stream,
+                "200 OK",
+                "Logged in to monz0. You can close this window.",
+            )
+            .await?;
+            return Ok(Callback { code, state });
+        }
+
+        respond(&mut stream, "400 Bad Request", "missing authorisation code").await?;
+    }
+}
+
+/// Read the request line of an HTTP request, and return the request target
+async fn request_target(stream: &mut TcpStream) -> anyhow::Result<Option<String>> {
+    let mut lines = BufReader::new(stream).lines();
+
+    let request_line = match lines.next_line().await? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+
+    // skip the headers
+    while let Some(line) = lines.next_line().await? {
+        if line.is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => Ok(Some(target.to_string())),
+        _ => Ok(None),
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> anyhow::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\ncontent-type: text/plain; charset=utf-8\r\ncontent-length: \
+         {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Monzo only grants access once the user approves it in the Monzo app. Until
+/// then, requests are rejected with '403 Forbidden'.
+async fn wait_for_approval(client: &Client, timeout: Duration) -> anyhow::Result<()> {
+    let mut prompted = false;
+
+    let approval = async {
+        loop {
+            match client.accounts().await {
+                Ok(_) => return Ok(()),
+                Err(Error::Permanent(Cause::Api { status: 403, .. })) => {
+                    if !prompted {
+                        println!("Approve access for monz0 in the Monzo app to continue ...");
+                        prompted = true;
+                    }
+                    time::sleep(APPROVAL_POLL_INTERVAL).await;
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+    };
+
+    time::timeout(timeout, approval).await.context(
+        "timed out waiting for access to be approved in the Monzo app; the credentials are saved, \
+         and can be used once access is approved",
+    )?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use monz0_lib::{Auth, Client};
+    use monz0_test_server::{Fixture, Server};
+    use tokio::{
+        io::AsyncWriteExt,
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::{receive_callback, wait_for_approval};
+
+    fn server(awaiting_approval: bool) -> Server {
+        let mut fixture =
+            Fixture::from_yaml(include_str!("../../monz0-test-server/fixtures/basic.yml")).unwrap();
+        fixture.auth.awaiting_approval = awaiting_approval;
+        Server::start(fixture)
+    }
+
+    fn client(server: &Server) -> Client {
+        let auth = Auth::Basic {
+            access_token: "ACCESS_TOKEN".to_string(),
+        };
+        Client::with_url(auth, server.url())
+    }
+
+    #[tokio::test]
+    async fn approved() {
+        let server = server(false);
+        wait_for_approval(&client(&server), Duration::from_secs(5))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn approval_timeout() {
+        let server = server(true);
+
+        let error = wait_for_approval(&client(&server), Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("timed out waiting for access"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_connections_are_skipped() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let callback = tokio::spawn(async move { receive_callback(&listener).await });
+
+        // a connection which never sends a request, like a browser's preconnect
+        let _idle = TcpStream::connect(addr).await.unwrap();
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /callback?code=CODE&state=STATE HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+
+        let callback = callback.await.unwrap().unwrap();
+        assert_eq!(callback.code, "CODE");
+        assert_eq!(callback.state, "STATE");
+    }
+}
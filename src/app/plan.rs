@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use monz0_lib::{state, OwnedLedger};
+use tracing::instrument;
+
+use crate::{
+    config::{self, Options},
+    operation::Op,
+    plan,
+};
+
+/// Compute the transfers for each operation, and save them as a plan that can
+/// be applied later
+#[derive(Debug, Parser, Clone)]
+pub struct Plan {
+    /// The path to write the plan to
+    #[clap(long, short, default_value = "plan.json")]
+    out: PathBuf,
+}
+
+impl Plan {
+    #[instrument(skip(self))]
+    pub async fn run(self, options: &Options) -> anyhow::Result<()> {
+        let config = config::load(options)?;
+        let client = config::client(options, &config)?;
+        let operations = config.operations;
+
+        tracing::info!("operations: {:#?}", &operations);
+
+        let state = client
+            .state_of(operations.iter().flat_map(Op::account_ids))
+            .await?;
+
+        // each step is computed from the state projected by the previous steps
+        let mut projected = state.clone();
+        let mut steps = Vec::default();
+        for op in &operations {
+            let ledger = OwnedLedger::from(op.transactions(&projected)?);
+            projected = state::project(&projected, &ledger)?;
+            steps.push(plan::Step::new(op.name(), ledger));
+        }
+
+        let plan = plan::Plan::new(&state, steps);
+
+        print!("{}", plan);
+        plan.save(&self.out)?;
+        println!("plan saved to '{}'", self.out.display());
+
+        Ok(())
+    }
+}
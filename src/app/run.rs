@@ -1,8 +1,12 @@
 use clap::Parser;
-use monz0_lib::{Client, Ledger, Pot};
+use monz0_lib::{state, OwnedLedger};
 use tracing::instrument;
 
-use crate::config;
+use crate::{
+    config::{self, Options},
+    operation::Op,
+    summary::{state_summary, transactions_summary},
+};
 
 #[derive(Debug, Parser, Clone, Copy)]
 pub struct Run {
@@ -12,17 +16,24 @@ pub struct Run {
 
 impl Run {
     #[instrument(skip(self))]
-    pub async fn run(self) -> anyhow::Result<()> {
-        let client: Client = config::auth()?.into();
-        let operations = config::operations()?;
+    pub async fn run(self, options: &Options) -> anyhow::Result<()> {
+        let config = config::load(options)?;
+        let client = config::client(options, &config)?;
+        let operations = config.operations;
 
         tracing::info!("config: {:#?}", &self);
         tracing::info!("operations: {:#?}", &operations);
 
+        // a single snapshot is fetched, and projected forward as each ledger is
+        // processed
+        let mut state = client
+            .state_of(operations.iter().flat_map(Op::account_ids))
+            .await?;
+
         for op in &operations {
-            let state = client.state().await?;
             println!("Running {}", op.name());
-            let ledger = op.transactions(&state)?;
+            let ledger = OwnedLedger::from(op.transactions(&state)?);
+            let projected = state::project(&state, &ledger)?;
 
             if self.dry_run {
                 println!("{}", transactions_summary(&ledger));
@@ -31,32 +42,19 @@ impl Run {
                 println!("nothing to do ...");
             } else {
                 println!("{}", transactions_summary(&ledger));
+                ledger.validate(&state)?;
                 client.process_ledger(&ledger).await?;
             }
+
+            // later operations see the effects of the earlier ones, even in a
+            // dry run
+            state = projected;
         }
 
-        config::save_auth(&client.auth().await)?;
+        if self.dry_run {
+            println!("projected balances:\n{}", state_summary(&state));
+        }
 
         Ok(())
     }
 }
-
-fn transactions_summary(ledger: &Ledger) -> String {
-    let mut summary = String::new();
-
-    for (account_id, transactions) in ledger {
-        summary += &format!("{}:\n", account_id);
-
-        for (pot, amount) in transactions {
-            summary += &format!("{}: {}\n", &pot.name, &format_currency(pot, amount));
-        }
-    }
-
-    summary
-}
-
-fn format_currency(pot: &Pot, amount: i64) -> String {
-    let currency = rusty_money::iso::find(&pot.currency).expect("unexpected currency ISO code");
-    let money = rusty_money::Money::from_minor(amount, currency);
-    format!("{}", money)
-}
@@ -1,9 +1,17 @@
-use confy::ConfyError;
+use crate::{
+    config::{self, Options},
+    credentials::Source,
+};
 
-use crate::config;
+pub fn run(options: &Options) -> anyhow::Result<()> {
+    let config = config::load(options)?;
 
-pub fn run() -> Result<(), ConfyError> {
-    let operations = config::operations()?;
-    println!("{:#?}", operations);
+    if let Some(profile) = &options.profile {
+        println!("profile: {}", profile);
+    }
+    println!("config: {}", options.config_path()?.display());
+    println!("credentials: {}", Source::new(options, config.credentials)?);
+
+    println!("{:#?}", config);
     Ok(())
 }
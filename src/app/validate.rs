@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use monz0_lib::{validate, State};
+use tracing::instrument;
+
+use crate::{
+    config::{self, Config, Options},
+    operation::Op,
+};
+
+/// Check the config file for mistakes
+///
+/// The config is parsed, and the operations are checked for pots used by more
+/// than one operation. The pots can also be checked against the state of the
+/// accounts, either fetched from Monzo or loaded from a file.
+#[derive(Debug, Parser, Clone)]
+pub struct Validate {
+    /// Check that the pots exist and are usable, using the current state of
+    /// the accounts in Monzo
+    #[clap(long, conflicts_with = "state")]
+    live: bool,
+
+    /// Check that the pots exist and are usable, using a saved state of the
+    /// accounts. This is a YAML or JSON map of account IDs to the balance and
+    /// pots, as returned by the Monzo API.
+    #[clap(long, parse(from_os_str))]
+    state: Option<PathBuf>,
+}
+
+impl Validate {
+    #[instrument(skip(self))]
+    pub async fn run(self, options: &Options) -> anyhow::Result<()> {
+        let path = options.config_path()?;
+        let config = parse(&path)?;
+
+        let pots: Vec<_> = config.operations.iter().map(Op::pots).collect();
+        let mut problems = validate::conflicts(&pots);
+
+        let state = if self.live {
+            let client = config::client(options, &config)?;
+            Some(
+                client
+                    .state_of(config.operations.iter().flat_map(Op::account_ids))
+                    .await?,
+            )
+        } else if let Some(path) = &self.state {
+            Some(load_state(path)?)
+        } else {
+            None
+        };
+
+        if let Some(state) = &state {
+            problems.extend(validate::check(&pots, state));
+        }
+
+        if problems.is_empty() {
+            println!("{}: ok", path.display());
+            return Ok(());
+        }
+
+        problems.sort_by_key(|(index, _)| *index);
+        for (index, problem) in &problems {
+            println!(
+                "{}: operation {} ({}): {}",
+                path.display(),
+                index + 1,
+                config.operations[*index].name(),
+                problem
+            );
+        }
+
+        bail!("found {} problem(s) in the config", problems.len())
+    }
+}
+
+/// Parse the config file, reporting any error with its location in the file
+fn parse(path: &Path) -> anyhow::Result<Config> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read '{}'", path.display()))?;
+
+    config::parse(&contents).map_err(|error| {
+        let message = error.to_string();
+
+        match error
+            .downcast_ref::<serde_yaml::Error>()
+            .and_then(serde_yaml::Error::location)
+        {
+            Some(location) => {
+                let suffix = format!(" at line {} column {}", location.line(), location.column());
+                anyhow::anyhow!(
+                    "{}:{}:{}: {}",
+                    path.display(),
+                    location.line(),
+                    location.column(),
+                    message.strip_suffix(&suffix).unwrap_or(&message)
+                )
+            }
+            None => anyhow::anyhow!("{}: {:#}", path.display(), error),
+        }
+    })
+}
+
+fn load_state(path: &Path) -> anyhow::Result<State> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read '{}'", path.display()))?;
+
+    // YAML is a superset of JSON, so this handles both
+    serde_yaml::from_str(&contents)
+        .with_context(|| format!("invalid state in '{}'", path.display()))
+}
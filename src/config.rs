@@ -1,17 +1,351 @@
-use monz0_lib::Auth;
+use std::{convert::TryFrom, path::PathBuf, sync::Arc};
 
-use crate::operation::Op;
+use anyhow::{bail, Context};
+use monz0_lib::{Auth, Client, RateLimit, RetryPolicy};
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Schema, SchemaObject},
+    JsonSchema,
+};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+
+use crate::{
+    credentials::{CredentialStore, Source, Store},
+    operation::Op,
+};
+
+pub mod migrate;
 
 pub static BIN_NAME: &str = std::env!("CARGO_PKG_NAME");
 
-pub fn operations() -> Result<Vec<Op>, confy::ConfyError> {
-    confy::load(BIN_NAME, "config")
+/// Global options for where the config and credentials are loaded from, and
+/// which Monzo API they're used with
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct Options {
+    /// Override the base URL of the Monzo API
+    #[clap(long, global = true, env = "MONZ0_API_URL")]
+    pub api_url: Option<String>,
+
+    /// Load the config from this file, rather than the config directory
+    #[clap(long, global = true, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Load the credentials from this YAML file, rather than the environment
+    /// or the configured credential store
+    #[clap(long, alias = "auth-file", global = true, parse(from_os_str))]
+    pub auth: Option<PathBuf>,
+
+    /// Use a named profile, with its own config and credentials stored in
+    /// 'profiles/<PROFILE>' in the config directory
+    #[clap(long, global = true, env = "MONZ0_PROFILE", validator = validate_profile)]
+    pub profile: Option<String>,
+}
+
+impl Options {
+    pub fn api_url(&self) -> Option<&str> {
+        self.api_url.as_deref()
+    }
+
+    /// The directory holding the config and credentials of the selected
+    /// profile
+    pub fn profile_dir(&self) -> anyhow::Result<PathBuf> {
+        let dir = directories_next::ProjectDirs::from("rs", "", BIN_NAME)
+            .map(|dirs| dirs.config_dir().to_path_buf())
+            .context("failed to find the config directory")?;
+
+        Ok(match &self.profile {
+            Some(profile) => dir.join("profiles").join(profile),
+            None => dir,
+        })
+    }
+
+    /// The path of the config file
+    pub fn config_path(&self) -> anyhow::Result<PathBuf> {
+        match &self.config {
+            Some(path) => Ok(path.clone()),
+            None => Ok(self.profile_dir()?.join("config.yml")),
+        }
+    }
+}
+
+/// Profile names become directory names, so are restricted to a safe set of
+/// characters
+fn validate_profile(profile: &str) -> Result<(), String> {
+    let valid = !profile.is_empty()
+        && profile
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err("profile names may only contain letters, digits, '-' and '_'".to_string())
+    }
+}
+
+/// The contents of the config file, in the current format.
+///
+/// Files in older formats are migrated as they are loaded; see [`migrate`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(try_from = "Settings")]
+pub struct Config {
+    /// How requests to the Monzo API are retried
+    pub retry: RetryPolicy,
+
+    /// Limits on the rate and concurrency of requests to the Monzo API
+    pub rate_limit: RateLimit,
+
+    /// Where the credentials are stored
+    pub credentials: Store,
+
+    pub operations: Vec<Op>,
+}
+
+/// The config file as written, including the version of its format
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(title = "monz0 config")]
+struct Settings {
+    /// The version of the config format. Run `monz0 config migrate` to update
+    /// older config files.
+    #[schemars(schema_with = "version_schema")]
+    version: u64,
+
+    /// How requests to the Monzo API are retried
+    #[serde(default)]
+    retry: RetryPolicy,
+
+    /// Limits on the rate and concurrency of requests to the Monzo API
+    #[serde(default)]
+    rate_limit: RateLimit,
+
+    /// Where the credentials are stored
+    #[serde(default)]
+    credentials: Store,
+
+    /// The operations to run, in order
+    #[serde(default)]
+    operations: Vec<Op>,
+}
+
+impl TryFrom<Settings> for Config {
+    type Error = String;
+
+    fn try_from(settings: Settings) -> Result<Self, Self::Error> {
+        if settings.version != migrate::CURRENT_VERSION {
+            return Err(format!(
+                "expected version {} of the config format, found version {}",
+                migrate::CURRENT_VERSION,
+                settings.version
+            ));
+        }
+
+        Ok(Self {
+            retry: settings.retry,
+            rate_limit: settings.rate_limit,
+            credentials: settings.credentials,
+            operations: settings.operations,
+        })
+    }
+}
+
+impl Serialize for Config {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut settings = serializer.serialize_struct("Config", 5)?;
+        settings.serialize_field("version", &migrate::CURRENT_VERSION)?;
+        settings.serialize_field("retry", &self.retry)?;
+        settings.serialize_field("rate_limit", &self.rate_limit)?;
+        settings.serialize_field("credentials", &self.credentials)?;
+        settings.serialize_field("operations", &self.operations)?;
+        settings.end()
+    }
+}
+
+impl JsonSchema for Config {
+    fn schema_name() -> String {
+        Settings::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        Settings::json_schema(gen)
+    }
 }
 
-pub fn auth() -> Result<Auth, confy::ConfyError> {
-    confy::load(BIN_NAME, "auth")
+/// Only the current version of the config format can be described by the
+/// schema, since older versions are migrated before they are parsed
+fn version_schema(_: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Integer.into()),
+        const_value: Some(migrate::CURRENT_VERSION.into()),
+        ..SchemaObject::default()
+    }
+    .into()
 }
 
-pub fn save_auth(auth: &Auth) -> Result<(), confy::ConfyError> {
-    confy::store(BIN_NAME, "auth", auth)
+/// Parse the contents of a config file, migrating it from an older format if
+/// necessary
+pub fn parse(contents: &str) -> anyhow::Result<Config> {
+    let config = migrate::read(contents)?;
+
+    // an empty file, or one with only comments
+    if config.is_null() {
+        return Ok(Config::default());
+    }
+
+    if migrate::version(&config)? == migrate::CURRENT_VERSION {
+        // parse the contents directly, so that errors have their location
+        return Ok(serde_yaml::from_str(contents)?);
+    }
+
+    let (version, config) = migrate::migrate(config)?;
+    tracing::warn!(
+        "the config is in version {} of the format; run `{} config migrate` to update it",
+        version,
+        BIN_NAME
+    );
+
+    // the migrated config has no location in the file, so neither do errors
+    serde_yaml::from_value(config).with_context(|| {
+        format!(
+            "the config was migrated from version {} of the format, so the line and column of \
+             this error aren't known",
+            version
+        )
+    })
+}
+
+/// Load the config file. If the default config file doesn't exist, an empty
+/// one is created.
+pub fn load(options: &Options) -> anyhow::Result<Config> {
+    let path = options.config_path()?;
+
+    if !path.exists() {
+        if options.config.is_some() {
+            bail!("config file '{}' doesn't exist", path.display());
+        }
+
+        let config = Config::default();
+        confy::store_path(&path, &config)
+            .with_context(|| format!("failed to create '{}'", path.display()))?;
+        return Ok(config);
+    }
+
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read '{}'", path.display()))?;
+    parse(&contents).with_context(|| format!("failed to load '{}'", path.display()))
+}
+
+/// Create a [`Client`] from the credentials, in order of precedence, in the
+/// `--auth` file, the environment, or the configured credential store
+pub fn client(options: &Options, config: &Config) -> anyhow::Result<Client> {
+    let store = Source::new(options, config.credentials)?.open()?;
+
+    Ok(client_with_auth(store.load()?, options.api_url(), store)
+        .with_retry_policy(config.retry)
+        .with_rate_limit(config.rate_limit))
+}
+
+/// Create a [`Client`] from the given credentials, optionally overriding the
+/// base URL of the Monzo API.
+///
+/// The credentials are saved to the `store` as soon as they are refreshed,
+/// since the old refresh token may no longer be valid.
+pub fn client_with_auth(
+    auth: Auth,
+    api_url: Option<&str>,
+    store: Arc<dyn CredentialStore>,
+) -> Client {
+    let client = match api_url {
+        Some(url) => Client::with_url(auth, url),
+        None => Client::from(auth),
+    };
+
+    client.with_refresh_hook(move |auth| {
+        if let Err(error) = store.save(auth) {
+            tracing::error!("failed to save the refreshed credentials: {}", error);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use monz0_lib::{RateLimit, RetryPolicy};
+
+    use super::{parse, validate_profile, Config};
+    use crate::credentials::Store;
+
+    #[test]
+    fn profile_names() {
+        assert!(validate_profile("joint").is_ok());
+        assert!(validate_profile("personal_2").is_ok());
+
+        assert!(validate_profile("").is_err());
+        assert!(validate_profile("../joint").is_err());
+        assert!(validate_profile("a/b").is_err());
+    }
+
+    #[test]
+    fn deserialise_operations() {
+        let raw = "
+    - sweep:
+        account_goal: 10000
+        pots:
+        - bills
+";
+
+        let config = parse(raw).unwrap();
+        assert_eq!(config.operations.len(), 1);
+        assert_eq!(config.retry, RetryPolicy::default());
+        assert_eq!(config.rate_limit, RateLimit::default());
+        assert_eq!(config.credentials, Store::Plaintext);
+    }
+
+    #[test]
+    fn deserialise_config() {
+        let raw = "
+    version: 2
+
+    retry:
+      max_attempts: 5
+      max_delay_ms: 30000
+
+    rate_limit:
+      requests_per_second: 2
+
+    credentials: encrypted
+
+    operations:
+    - sweep:
+        account_goal: 10000
+        pots:
+        - bills
+";
+
+        let config = parse(raw).unwrap();
+        assert_eq!(config.operations.len(), 1);
+        assert_eq!(config.retry.max_attempts, 5);
+        assert_eq!(config.retry.max_delay, Duration::from_secs(30));
+        assert_eq!(config.rate_limit.requests_per_second, 2);
+        assert_eq!(config.credentials, Store::Encrypted);
+    }
+
+    #[test]
+    fn empty() {
+        for raw in ["", "# no settings yet\n"] {
+            let config = parse(raw).unwrap();
+            assert!(config.operations.is_empty());
+            assert_eq!(config.retry, RetryPolicy::default());
+        }
+    }
+
+    #[test]
+    fn serialise_default() {
+        let raw = serde_yaml::to_string(&Config::default()).unwrap();
+        assert!(raw.contains("version: 2\n"), "{}", raw);
+
+        let config = parse(&raw).unwrap();
+        assert!(config.operations.is_empty());
+    }
 }
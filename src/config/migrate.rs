@@ -0,0 +1,201 @@
+//! Migrating config files written in older formats
+//!
+//! Each format is identified by a version number. Files in the current format
+//! have a top-level `version` key; older files are recognised by their shape:
+//!
+//! - version 0: a plain list of operations
+//! - version 1: a map of settings, without a `version` key
+//! - version 2: a map of settings, with `version: 2`, where the sweep and ratio
+//!   operations' `current_account_goal` is called `account_goal`
+//!
+//! Migrations work on the YAML [`Value`], so they don't depend on the current
+//! operation types being able to read old configs.
+
+use anyhow::{bail, ensure, Context};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+/// The migration from each version to the next, indexed by the version it
+/// migrates from
+const MIGRATIONS: [fn(Value) -> anyhow::Result<Value>; 2] = [v0_to_v1, v1_to_v2];
+
+/// The version of the config format written by this version of monz0
+pub const CURRENT_VERSION: u64 = MIGRATIONS.len() as u64;
+
+/// Read the contents of a config file as YAML. A file with no YAML document,
+/// such as an empty file, is null.
+pub fn read(contents: &str) -> Result<Value, serde_yaml::Error> {
+    match serde_yaml::Deserializer::from_str(contents).next() {
+        Some(document) => Value::deserialize(document),
+        None => Ok(Value::Null),
+    }
+}
+
+/// The version of the format of a config file
+pub fn version(config: &Value) -> anyhow::Result<u64> {
+    let version = match config {
+        Value::Sequence(_) => 0,
+        Value::Mapping(settings) => match settings.get(&key("version")) {
+            None => 1,
+            Some(version) => version
+                .as_u64()
+                .with_context(|| format!("invalid config version {:?}", version))?,
+        },
+        // an empty file
+        Value::Null => CURRENT_VERSION,
+        _ => bail!("expected a map of settings, or a list of operations"),
+    };
+
+    ensure!(
+        version <= CURRENT_VERSION,
+        "the config is version {}, but this version of monz0 only understands up to version {}",
+        version,
+        CURRENT_VERSION
+    );
+
+    Ok(version)
+}
+
+/// Migrate a config to the current version, returning the version it was
+/// migrated from along with the migrated config
+pub fn migrate(mut config: Value) -> anyhow::Result<(u64, Value)> {
+    let from = version(&config)?;
+
+    let pending = (0..)
+        .zip(MIGRATIONS.iter())
+        .skip_while(|(version, _)| *version < from);
+    for (version, migration) in pending {
+        config = migration(config)
+            .with_context(|| format!("failed to migrate the config from version {}", version))?;
+    }
+
+    Ok((from, config))
+}
+
+/// Wrap the list of operations in a map of settings
+#[allow(clippy::unnecessary_wraps)]
+fn v0_to_v1(operations: Value) -> anyhow::Result<Value> {
+    let mut settings = Mapping::new();
+    settings.insert(key("operations"), operations);
+    Ok(Value::Mapping(settings))
+}
+
+/// Add the version, and rename the sweep and ratio operations'
+/// `current_account_goal`
+fn v1_to_v2(config: Value) -> anyhow::Result<Value> {
+    let mut settings = match config {
+        Value::Mapping(settings) => settings,
+        _ => bail!("expected a map of settings"),
+    };
+
+    if let Some(Value::Sequence(operations)) = settings.get_mut(&key("operations")) {
+        for (index, operation) in operations.iter_mut().enumerate() {
+            for name in ["sweep", "ratio"] {
+                if let Some(Value::Mapping(op)) = operation.get_mut(name) {
+                    rename(op, "current_account_goal", "account_goal")
+                        .with_context(|| format!("in operation {}", index + 1))?;
+                }
+            }
+        }
+    }
+
+    // put the version first, so it's the first thing seen in the file
+    let mut migrated = Mapping::new();
+    migrated.insert(key("version"), Value::from(2_u64));
+    migrated.extend(settings);
+    Ok(Value::Mapping(migrated))
+}
+
+fn rename(map: &mut Mapping, from: &str, to: &str) -> anyhow::Result<()> {
+    if let Some(value) = map.remove(&key(from)) {
+        ensure!(
+            !map.contains_key(&key(to)),
+            "both '{}' and '{}' are set",
+            from,
+            to
+        );
+        map.insert(key(to), value);
+    }
+
+    Ok(())
+}
+
+fn key(name: &str) -> Value {
+    Value::String(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml::Value;
+
+    use super::{migrate, version, CURRENT_VERSION};
+    use crate::config;
+
+    const V0: &str = include_str!("../../tests/fixtures/config/v0.yml");
+    const V1: &str = include_str!("../../tests/fixtures/config/v1.yml");
+    const V2: &str = include_str!("../../tests/fixtures/config/v2.yml");
+
+    fn yaml(raw: &str) -> Value {
+        serde_yaml::from_str(raw).unwrap()
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(version(&yaml(V0)).unwrap(), 0);
+        assert_eq!(version(&yaml(V1)).unwrap(), 1);
+        assert_eq!(version(&yaml(V2)).unwrap(), 2);
+
+        assert!(version(&yaml("version: 3")).is_err());
+        assert!(version(&yaml("version: two")).is_err());
+        assert!(version(&yaml("monz0")).is_err());
+    }
+
+    #[test]
+    fn migrate_v0() {
+        let (from, migrated) = migrate(yaml(V0)).unwrap();
+
+        assert_eq!(from, 0);
+        assert_eq!(migrated["version"], Value::from(CURRENT_VERSION));
+        assert_eq!(migrated["operations"][0], yaml(V2)["operations"][0]);
+        config::parse(&serde_yaml::to_string(&migrated).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn migrate_v1() {
+        let (from, migrated) = migrate(yaml(V1)).unwrap();
+
+        assert_eq!(from, 1);
+        assert_eq!(migrated, yaml(V2));
+    }
+
+    #[test]
+    fn migrate_current() {
+        assert_eq!(migrate(yaml(V2)).unwrap(), (CURRENT_VERSION, yaml(V2)));
+    }
+
+    #[test]
+    fn conflicting_rename() {
+        let raw = "
+    - ratio:
+        account_goal: 50
+        current_account_goal: 50
+        pots:
+          holiday: 1
+";
+
+        let error = migrate(yaml(raw)).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "failed to migrate the config from version 1: in operation 1: both \
+             'current_account_goal' and 'account_goal' are set"
+        );
+    }
+
+    #[test]
+    fn load_historical_formats() {
+        for (raw, operations) in [(V0, 1), (V1, 2), (V2, 2)] {
+            let config = config::parse(raw).unwrap();
+            assert_eq!(config.operations.len(), operations);
+        }
+    }
+}
@@ -0,0 +1,404 @@
+//! Pluggable storage for the Monzo API credentials
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context};
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use monz0_lib::Auth;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Options, BIN_NAME};
+
+/// The environment variable holding the passphrase for encrypted credentials.
+/// If it's not set, the user is prompted for the passphrase.
+const PASSPHRASE_VAR: &str = "MONZ0_PASSPHRASE";
+
+/// The environment variables which supply the credentials. If the refresh
+/// token is set, the client ID and secret are required too.
+const ACCESS_TOKEN_VAR: &str = "MONZ0_ACCESS_TOKEN";
+const REFRESH_TOKEN_VAR: &str = "MONZ0_REFRESH_TOKEN";
+const CLIENT_ID_VAR: &str = "MONZ0_CLIENT_ID";
+const CLIENT_SECRET_VAR: &str = "MONZ0_CLIENT_SECRET";
+
+/// Where the credentials are stored, chosen in the config file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Store {
+    /// A plaintext YAML file ('auth.yml') in the config directory
+    #[default]
+    Plaintext,
+
+    /// A file ('auth.enc') in the config directory, encrypted with a
+    /// passphrase
+    Encrypted,
+
+    /// The OS keyring (Secret Service on Linux). Requires the 'keyring'
+    /// feature.
+    Keyring,
+}
+
+/// Where the credentials are loaded from and saved to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Environment variables. The credentials can't be saved.
+    Environment,
+
+    /// A plaintext YAML file
+    File(PathBuf),
+
+    /// A file encrypted with a passphrase
+    Encrypted(PathBuf),
+
+    /// An entry in the OS keyring, for the given user
+    Keyring(String),
+}
+
+impl Source {
+    /// Find the credentials to load. In order of precedence:
+    ///
+    /// 1. the `--auth` file, if given
+    /// 2. the environment, if `MONZ0_ACCESS_TOKEN` is set
+    /// 3. the configured `store`, for the selected profile
+    pub fn new(options: &Options, store: Store) -> anyhow::Result<Self> {
+        if options.auth.is_none() && var(ACCESS_TOKEN_VAR).is_some() {
+            return Ok(Self::Environment);
+        }
+
+        Self::persistent(options, store)
+    }
+
+    /// Find where new credentials should be saved: the `--auth` file if given,
+    /// otherwise the configured `store`. Credentials can't be saved to the
+    /// environment, so it's ignored.
+    pub fn persistent(options: &Options, store: Store) -> anyhow::Result<Self> {
+        if let Some(path) = &options.auth {
+            return Ok(Self::File(path.clone()));
+        }
+
+        let dir = options.profile_dir()?;
+        Ok(match store {
+            Store::Plaintext => Self::File(dir.join("auth.yml")),
+            Store::Encrypted => Self::Encrypted(dir.join("auth.enc")),
+            Store::Keyring => Self::Keyring(match &options.profile {
+                Some(profile) => format!("auth-{}", profile),
+                None => "auth".to_string(),
+            }),
+        })
+    }
+
+    /// Open the [`CredentialStore`], prompting for a passphrase if required
+    pub fn open(&self) -> anyhow::Result<Arc<dyn CredentialStore>> {
+        Ok(match self {
+            Self::Environment => Arc::new(Environment),
+            Self::File(path) => Arc::new(File { path: path.clone() }),
+            Self::Encrypted(path) => Arc::new(EncryptedFile::new(path.clone(), passphrase()?)),
+            Self::Keyring(user) => keyring(user)?,
+        })
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Environment => write!(f, "environment variables"),
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Encrypted(path) => write!(f, "{} (encrypted)", path.display()),
+            Self::Keyring(user) => write!(f, "keyring entry '{}/{}'", BIN_NAME, user),
+        }
+    }
+}
+
+/// Loads and saves the [`Auth`] credentials
+pub trait CredentialStore: fmt::Debug + Send + Sync {
+    fn load(&self) -> anyhow::Result<Auth>;
+
+    fn save(&self, auth: &Auth) -> anyhow::Result<()>;
+}
+
+/// Credentials in a plaintext YAML file at a given path
+#[derive(Debug)]
+struct File {
+    path: PathBuf,
+}
+
+impl CredentialStore for File {
+    fn load(&self) -> anyhow::Result<Auth> {
+        let contents = std::fs::read_to_string(&self.path).with_context(|| {
+            format!(
+                "failed to read credentials from '{}' (run 'monz0 login' first)",
+                self.path.display()
+            )
+        })?;
+        serde_yaml::from_str(&contents)
+            .with_context(|| format!("invalid credentials in '{}'", self.path.display()))
+    }
+
+    fn save(&self, auth: &Auth) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_private(&self.path, serde_yaml::to_string(auth)?.as_bytes())
+            .with_context(|| format!("failed to write '{}'", self.path.display()))
+    }
+}
+
+/// Credentials supplied by environment variables, for running in containers
+/// and from cron.
+///
+/// Refreshed credentials can't be saved, so refreshable credentials only last
+/// as long as the refresh token does.
+#[derive(Debug)]
+struct Environment;
+
+impl CredentialStore for Environment {
+    fn load(&self) -> anyhow::Result<Auth> {
+        let access_token = required(ACCESS_TOKEN_VAR)?;
+
+        Ok(match var(REFRESH_TOKEN_VAR) {
+            Some(refresh_token) => Auth::refreshable(
+                access_token,
+                required(CLIENT_ID_VAR)?,
+                required(CLIENT_SECRET_VAR)?,
+                refresh_token,
+            ),
+            None => Auth::Basic { access_token },
+        })
+    }
+
+    fn save(&self, _auth: &Auth) -> anyhow::Result<()> {
+        tracing::warn!(
+            "the credentials were refreshed, but can't be saved to the environment. Update {} and \
+             {}, or use a credential store.",
+            ACCESS_TOKEN_VAR,
+            REFRESH_TOKEN_VAR
+        );
+        Ok(())
+    }
+}
+
+/// An environment variable, treating an empty value as unset
+fn var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn required(name: &str) -> anyhow::Result<String> {
+    var(name).with_context(|| format!("{} must be set", name))
+}
+
+/// Credentials encrypted at rest with ChaCha20-Poly1305, using a key derived
+/// from a passphrase with Argon2.
+///
+/// The file holds a random salt, followed by a random nonce, followed by the
+/// encrypted YAML.
+struct EncryptedFile {
+    path: PathBuf,
+    passphrase: String,
+}
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+impl EncryptedFile {
+    fn new(path: PathBuf, passphrase: String) -> Self {
+        Self { path, passphrase }
+    }
+
+    fn cipher(&self, salt: &[u8]) -> anyhow::Result<ChaCha20Poly1305> {
+        let mut key = [0_u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(self.passphrase.as_bytes(), salt, &mut key)
+            .map_err(|error| anyhow::anyhow!("failed to derive key: {}", error))?;
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+}
+
+impl fmt::Debug for EncryptedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedFile")
+            .field("path", &self.path)
+            .field("passphrase", &"<redacted>")
+            .finish()
+    }
+}
+
+impl CredentialStore for EncryptedFile {
+    fn load(&self) -> anyhow::Result<Auth> {
+        let contents = std::fs::read(&self.path).with_context(|| {
+            format!(
+                "failed to read credentials from '{}' (run 'monz0 login' first)",
+                self.path.display()
+            )
+        })?;
+
+        if contents.len() < SALT_LEN + NONCE_LEN {
+            bail!("'{}' is not a valid credentials file", self.path.display());
+        }
+        let (salt, rest) = contents.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let plaintext = self
+            .cipher(salt)?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "failed to decrypt '{}' (wrong passphrase?)",
+                    self.path.display()
+                )
+            })?;
+
+        Ok(serde_yaml::from_slice(&plaintext)?)
+    }
+
+    fn save(&self, auth: &Auth) -> anyhow::Result<()> {
+        let salt: [u8; SALT_LEN] = rand::random();
+        let nonce: [u8; NONCE_LEN] = rand::random();
+
+        let plaintext = serde_yaml::to_vec(auth)?;
+        let ciphertext = self
+            .cipher(&salt)?
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| anyhow::anyhow!("failed to encrypt credentials"))?;
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_private(&self.path, &[&salt[..], &nonce, &ciphertext].concat())
+            .with_context(|| format!("failed to write '{}'", self.path.display()))
+    }
+}
+
+/// Write a file that only the current user can read
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)?.write_all(contents)
+}
+
+/// Credentials stored in the OS keyring
+#[cfg(feature = "keyring")]
+#[derive(Debug)]
+struct Keyring {
+    entry: keyring::Entry,
+}
+
+#[cfg(feature = "keyring")]
+impl CredentialStore for Keyring {
+    fn load(&self) -> anyhow::Result<Auth> {
+        let secret = self
+            .entry
+            .get_password()
+            .context("failed to read credentials from the keyring (run 'monz0 login' first)")?;
+        Ok(serde_yaml::from_str(&secret)?)
+    }
+
+    fn save(&self, auth: &Auth) -> anyhow::Result<()> {
+        self.entry
+            .set_password(&serde_yaml::to_string(auth)?)
+            .context("failed to save credentials to the keyring")
+    }
+}
+
+#[cfg(feature = "keyring")]
+#[allow(clippy::unnecessary_wraps)]
+fn keyring(user: &str) -> anyhow::Result<Arc<dyn CredentialStore>> {
+    Ok(Arc::new(Keyring {
+        entry: keyring::Entry::new(BIN_NAME, user),
+    }))
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring(_user: &str) -> anyhow::Result<Arc<dyn CredentialStore>> {
+    bail!("monz0 was built without keyring support (enable the 'keyring' feature)")
+}
+
+fn passphrase() -> anyhow::Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_VAR) {
+        return Ok(passphrase);
+    }
+
+    Ok(rpassword::prompt_password_stderr(
+        "passphrase for monz0 credentials: ",
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use monz0_lib::Auth;
+
+    use super::{CredentialStore, EncryptedFile, Source, Store};
+    use crate::config::Options;
+
+    fn auth() -> Auth {
+        serde_yaml::from_str(
+            "
+            access_token: ACCESS_TOKEN
+            client_id: CLIENT_ID
+            client_secret: CLIENT_SECRET
+            refresh_token: REFRESH_TOKEN
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("auth.enc");
+
+        let store = EncryptedFile::new(path.clone(), "hunter2".to_string());
+        store.save(&auth()).unwrap();
+
+        let contents = std::fs::read(&path).unwrap();
+        assert!(!String::from_utf8_lossy(&contents).contains("CLIENT_SECRET"));
+
+        let loaded = store.load().unwrap();
+        assert_eq!(
+            serde_yaml::to_string(&loaded).unwrap(),
+            serde_yaml::to_string(&auth()).unwrap()
+        );
+
+        let wrong = EncryptedFile::new(path, "password".to_string());
+        assert!(wrong.load().is_err());
+    }
+
+    #[test]
+    fn persistent_source() {
+        let options = Options {
+            profile: Some("joint".to_string()),
+            ..Options::default()
+        };
+
+        let file = Source::persistent(&options, Store::Plaintext).unwrap();
+        assert!(matches!(file, Source::File(path) if path.ends_with("profiles/joint/auth.yml")));
+
+        let keyring = Source::persistent(&options, Store::Keyring).unwrap();
+        assert_eq!(keyring, Source::Keyring("auth-joint".to_string()));
+
+        // an explicit auth file takes precedence over the store
+        let options = Options {
+            auth: Some(PathBuf::from("auth.yml")),
+            ..options
+        };
+        let file = Source::persistent(&options, Store::Encrypted).unwrap();
+        assert_eq!(file, Source::File(PathBuf::from("auth.yml")));
+    }
+}
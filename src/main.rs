@@ -8,8 +8,11 @@
 
 mod app;
 mod config;
+mod credentials;
 mod logging;
 mod operation;
+mod plan;
+mod summary;
 
 use app::App;
 
@@ -1,25 +1,56 @@
-use monz0_lib::{operation::Sweep, Ledger, Operation, State};
+use monz0_lib::{
+    operation::{Ratio, Sweep, TopUp},
+    validate::PotRef,
+    Ledger, Operation, State,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+/// An operation in the config file
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Op {
+    /// Sweep spare cash from the current account into pots, filling each to
+    /// its target in order
     Sweep(Sweep),
-    // Ratio(Ratio),
+
+    /// Split spare cash in the current account between pots, by weight
+    Ratio(Ratio),
+
+    /// Top up the current account from pots when its balance is low
+    TopUp(TopUp),
 }
 
 impl Op {
     pub fn name(&self) -> &'static str {
         match self {
             Self::Sweep(_) => Sweep::NAME,
-            // Self::Ratio(op) => op.name(),
+            Self::Ratio(_) => Ratio::NAME,
+            Self::TopUp(_) => TopUp::NAME,
+        }
+    }
+
+    pub fn account_ids(&self) -> Vec<&str> {
+        match self {
+            Self::Sweep(op) => op.account_ids(),
+            Self::Ratio(op) => op.account_ids(),
+            Self::TopUp(op) => op.account_ids(),
+        }
+    }
+
+    pub fn pots(&self) -> Vec<PotRef<'_>> {
+        match self {
+            Self::Sweep(op) => op.pots(),
+            Self::Ratio(op) => op.pots(),
+            Self::TopUp(op) => op.pots(),
         }
     }
 
     pub fn transactions<'a>(&'a self, state: &'a State) -> anyhow::Result<Ledger> {
         match self {
             Self::Sweep(op) => Ok(op.transactions(state)?),
-            // Self::Ratio(op) => op.transactions(state),
+            Self::Ratio(op) => Ok(op.transactions(state)?),
+            Self::TopUp(op) => Ok(op.transactions(state)?),
         }
     }
 }
@@ -30,19 +61,6 @@ mod tests {
 
     #[test]
     fn deserialise_yaml() {
-        //     let raw = r#"
-        //     - sweep: account_goal: 10000
-
-        //         pots:
-        //         - bills
-        //         - lottery
-        //         - allowance
-        //         - student loan
-        //         - savings
-
-        //     - ratio: current_account_goal: 10000 pots: savings: 2 holiday: 1
-        // "#;
-
         let raw = r#"
     - sweep:
         account_goal: 10000
@@ -53,6 +71,30 @@ mod tests {
         - allowance
         - student loan
         - savings
+
+    - ratio:
+        account_goal: 10000
+
+        pots:
+          savings: 2
+          holiday: 1
+
+    - topup:
+        account_floor: 50
+        account_goal: 100
+
+        pots:
+          - name: buffer
+          - name: savings
+            minimum: 1000
+
+    - sweep:
+        account_goal: £100.50
+        pots:
+        - name: holiday
+          target: 500 GBP
+        - name: lottery
+          target: 1000p
 "#;
 
         serde_yaml::from_str::<Vec<Op>>(raw).unwrap();
@@ -0,0 +1,253 @@
+//! Serialisable execution plans, used by the 'plan' and 'apply' subcommands
+
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use anyhow::{bail, Context};
+use monz0_lib::{OwnedLedger, State};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::summary::transactions_summary;
+
+/// A plan of the transfers to be made by each operation, along with the
+/// balances it was computed from.
+///
+/// A plan can only be applied if the balances haven't changed since it was
+/// created.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Plan {
+    /// The balances of the accounts and pots that the plan was computed from,
+    /// keyed by account ID
+    balances: BTreeMap<String, Balances>,
+
+    /// The operations, in the order they should be applied
+    operations: Vec<Step>,
+
+    /// A hash of the balances and operations, used to detect modified plans
+    hash: String,
+}
+
+/// The balance of an account and its pots
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Balances {
+    balance: i64,
+
+    /// pot balances, keyed by pot ID
+    pots: BTreeMap<String, i64>,
+}
+
+/// The transfers made by a single operation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Step {
+    name: String,
+    ledger: OwnedLedger,
+}
+
+impl Step {
+    pub fn new(name: &str, ledger: OwnedLedger) -> Self {
+        Self {
+            name: name.to_string(),
+            ledger,
+        }
+    }
+}
+
+impl Plan {
+    /// Create a new [`Plan`] from the given operation steps, recording the
+    /// balances of every account they touch.
+    pub fn new(state: &State, operations: Vec<Step>) -> Self {
+        let mut balances = BTreeMap::default();
+
+        for (account_id, _transactions) in operations.iter().flat_map(|step| &step.ledger) {
+            if let Some(account) = state.get(account_id) {
+                balances
+                    .entry(account_id.to_string())
+                    .or_insert_with(|| Balances::from(account));
+            }
+        }
+
+        let hash = hash(&balances, &operations);
+
+        Self {
+            balances,
+            operations,
+            hash,
+        }
+    }
+
+    /// Load a plan from a JSON file, checking that it hasn't been modified
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("failed to open plan '{}'", path.display()))?;
+        let plan: Self = serde_json::from_reader(file)
+            .with_context(|| format!("failed to parse plan '{}'", path.display()))?;
+
+        if hash(&plan.balances, &plan.operations) != plan.hash {
+            bail!("plan '{}' has been modified", path.display());
+        }
+
+        Ok(plan)
+    }
+
+    /// Save the plan as a JSON file
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("failed to create plan '{}'", path.display()))?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Returns true if the plan contains no transfers
+    pub fn is_empty(&self) -> bool {
+        self.operations.iter().all(|step| step.ledger.is_empty())
+    }
+
+    /// The IDs of the accounts that the plan touches
+    pub fn account_ids(&self) -> impl Iterator<Item = &str> {
+        self.balances.keys().map(String::as_str)
+    }
+
+    /// Describe any differences between the balances the plan was computed
+    /// from and the given [`State`]
+    pub fn drift(&self, state: &State) -> Vec<String> {
+        let mut drift = Vec::default();
+
+        for (account_id, planned) in &self.balances {
+            let current = if let Some(account) = state.get(account_id) {
+                Balances::from(account)
+            } else {
+                drift.push(format!("account {} no longer exists", account_id));
+                continue;
+            };
+
+            if current.balance != planned.balance {
+                drift.push(format!(
+                    "account {}: balance was {}, now {}",
+                    account_id, planned.balance, current.balance
+                ));
+            }
+
+            for (pot_id, balance) in &planned.pots {
+                match current.pots.get(pot_id) {
+                    Some(current) if current == balance => (),
+                    Some(current) => drift.push(format!(
+                        "pot {}: balance was {}, now {}",
+                        pot_id, balance, current
+                    )),
+                    None => drift.push(format!("pot {} no longer exists", pot_id)),
+                }
+            }
+        }
+
+        drift
+    }
+
+    /// The name and [`OwnedLedger`] of each operation, in order
+    pub fn ledgers(&self) -> impl Iterator<Item = (&str, &OwnedLedger)> {
+        self.operations
+            .iter()
+            .map(|step| (step.name.as_str(), &step.ledger))
+    }
+}
+
+impl From<&monz0_lib::state::Account> for Balances {
+    fn from(account: &monz0_lib::state::Account) -> Self {
+        Self {
+            balance: account.balance.balance,
+            pots: account
+                .pots
+                .iter()
+                .filter(|pot| !pot.deleted)
+                .map(|pot| (pot.id.clone(), pot.balance))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, ledger) in self.ledgers() {
+            writeln!(f, "{}:", name)?;
+
+            if ledger.is_empty() {
+                writeln!(f, "nothing to do ...")?;
+            } else {
+                write!(f, "{}", transactions_summary(ledger))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn hash(balances: &BTreeMap<String, Balances>, operations: &[Step]) -> String {
+    let bytes = serde_json::to_vec(&(balances, operations)).expect("failed to serialise plan");
+    format!("{:x}", Sha256::digest(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{hash, Balances, Plan, Step};
+
+    fn plan() -> Plan {
+        let mut pots = BTreeMap::default();
+        pots.insert("pot_1234".to_string(), 1000);
+
+        let mut balances = BTreeMap::default();
+        balances.insert(
+            "acc_1234".to_string(),
+            Balances {
+                balance: 20_000,
+                pots,
+            },
+        );
+
+        let ledger = "
+        accounts:
+          acc_1234:
+            withdrawals: []
+            deposits:
+              - pot_id: pot_1234
+                pot_name: Savings
+                currency: GBP
+                amount: 10000
+                dedupe_id: monz0-0000000000000000-0
+        ";
+
+        let operations = vec![Step::new("Sweep", serde_yaml::from_str(ledger).unwrap())];
+
+        let hash = hash(&balances, &operations);
+
+        Plan {
+            balances,
+            operations,
+            hash,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.json");
+
+        plan().save(&path).unwrap();
+        let plan = Plan::load(&path).unwrap();
+
+        assert!(!plan.is_empty());
+    }
+
+    #[test]
+    fn modified_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.json");
+
+        plan().save(&path).unwrap();
+
+        let raw = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, raw.replace("10000", "99999")).unwrap();
+
+        assert!(Plan::load(&path).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+//! Pretty-printing of ledgers and amounts
+
+use std::fmt::Write;
+
+use monz0_lib::{OwnedLedger, State};
+
+pub fn transactions_summary(ledger: &OwnedLedger) -> String {
+    let mut summary = String::new();
+
+    for (account_id, transactions) in ledger {
+        // writing to a `String` can't fail
+        writeln!(summary, "{}:", account_id).unwrap();
+
+        for (transfer, amount) in transactions.signed() {
+            writeln!(
+                summary,
+                "{}: {}",
+                transfer.pot_name,
+                format_currency(&transfer.currency, amount)
+            )
+            .unwrap();
+        }
+    }
+
+    summary
+}
+
+/// Summarise the balances of each account and its (active) pots
+pub fn state_summary(state: &State) -> String {
+    let mut summary = String::new();
+
+    let mut account_ids: Vec<_> = state.keys().collect();
+    account_ids.sort();
+
+    for account_id in account_ids {
+        let account = &state[account_id];
+        writeln!(
+            summary,
+            "{}: {}",
+            account_id,
+            format_currency(&account.balance.currency, account.balance.balance)
+        )
+        .unwrap();
+
+        for pot in account.pots.iter().filter(|pot| !pot.deleted) {
+            writeln!(
+                summary,
+                "  {}: {}",
+                pot.name,
+                format_currency(&pot.currency, pot.balance)
+            )
+            .unwrap();
+        }
+    }
+
+    summary
+}
+
+pub fn format_currency(currency: &str, amount: i64) -> String {
+    let currency = rusty_money::iso::find(currency).expect("unexpected currency ISO code");
+    let money = rusty_money::Money::from_minor(amount, currency);
+    format!("{}", money)
+}
@@ -0,0 +1,476 @@
+//! End-to-end tests of `monz0 run` against a fake Monzo API server
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    path::Path,
+    process::{Output, Stdio},
+};
+
+use monz0_test_server::{Fixture, Server};
+use tempfile::TempDir;
+
+const CONFIG: &str = r#"
+- sweep:
+    account_id: acc_1234
+    account_goal: 100
+    pots:
+      - bills
+      - savings
+"#;
+
+fn fixture(expired: bool) -> Fixture {
+    let mut fixture =
+        Fixture::from_yaml(include_str!("../monz0-test-server/fixtures/basic.yml")).unwrap();
+    fixture.auth.expired = expired;
+    fixture
+}
+
+/// Write the config and auth files into a temporary config directory
+fn config_dir(config: &str, auth: &str) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let config_dir = dir.path().join("monz0");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(config_dir.join("config.yml"), config).unwrap();
+    fs::write(config_dir.join("auth.yml"), auth).unwrap();
+    dir
+}
+
+fn monz0(config_dir: &Path, server: &Server, args: &[&str]) -> Output {
+    std::process::Command::new(env!("CARGO_BIN_EXE_monz0"))
+        .args(args)
+        .env("XDG_CONFIG_HOME", config_dir)
+        .env("MONZ0_API_URL", server.url())
+        .output()
+        .unwrap()
+}
+
+fn balances(server: &Server) -> Vec<i64> {
+    let account = &server.fixture().accounts["acc_1234"];
+    let mut balances = vec![account.balance];
+    balances.extend(account.pots.iter().map(|pot| pot.balance));
+    balances
+}
+
+const BASIC_AUTH: &str = "access_token: ACCESS_TOKEN\n";
+
+const REFRESHABLE_AUTH: &str = r#"
+access_token: ACCESS_TOKEN
+client_id: CLIENT_ID
+client_secret: CLIENT_SECRET
+refresh_token: REFRESH_TOKEN
+"#;
+
+#[test]
+fn run() {
+    let server = Server::start(fixture(false));
+    let dir = config_dir(CONFIG, BASIC_AUTH);
+
+    let output = monz0(dir.path(), &server, &["run"]);
+    assert!(output.status.success());
+
+    assert_eq!(balances(&server), vec![10_000, 5_000, 6_000, 0]);
+    assert_eq!(server.refreshes(), 0);
+}
+
+#[test]
+fn dry_run() {
+    let server = Server::start(fixture(false));
+    let dir = config_dir(CONFIG, BASIC_AUTH);
+
+    let output = monz0(dir.path(), &server, &["run", "--dry-run"]);
+    assert!(output.status.success());
+
+    assert_eq!(balances(&server), vec![20_000, 0, 1_000, 0]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("acc_1234: £100.00"), "{}", stdout);
+    assert!(stdout.contains("Savings: £60.00"), "{}", stdout);
+}
+
+#[test]
+fn refresh_expired_token() {
+    let server = Server::start(fixture(true));
+    let dir = config_dir(CONFIG, REFRESHABLE_AUTH);
+
+    let output = monz0(dir.path(), &server, &["run"]);
+    assert!(output.status.success());
+
+    assert_eq!(balances(&server), vec![10_000, 5_000, 6_000, 0]);
+    assert_eq!(server.refreshes(), 1);
+
+    // the rotated tokens are persisted
+    let auth = fs::read_to_string(dir.path().join("monz0").join("auth.yml")).unwrap();
+    assert!(auth.contains("REFRESH_TOKEN_1"));
+}
+
+#[test]
+fn refreshed_tokens_saved_on_failure() {
+    const MISSING_POT: &str = r#"
+- sweep:
+    account_id: acc_1234
+    account_goal: 100
+    pots:
+      - holiday
+"#;
+
+    let server = Server::start(fixture(true));
+    let dir = config_dir(MISSING_POT, REFRESHABLE_AUTH);
+
+    let output = monz0(dir.path(), &server, &["run"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("not found"));
+    assert_eq!(server.refreshes(), 1);
+
+    // the rotated tokens are persisted, even though the run failed
+    let auth = fs::read_to_string(dir.path().join("monz0").join("auth.yml")).unwrap();
+    assert!(auth.contains("REFRESH_TOKEN_1"));
+}
+
+#[test]
+fn expired_token_without_refresh() {
+    let server = Server::start(fixture(true));
+    let dir = config_dir(CONFIG, BASIC_AUTH);
+
+    let output = monz0(dir.path(), &server, &["run"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Running"));
+
+    assert_eq!(balances(&server), vec![20_000, 0, 1_000, 0]);
+}
+
+#[test]
+fn credentials_from_environment() {
+    let server = Server::start(fixture(true));
+    let dir = config_dir(CONFIG, "access_token: STALE_ACCESS_TOKEN\n");
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_monz0"))
+        .arg("run")
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("MONZ0_API_URL", server.url())
+        .env("MONZ0_ACCESS_TOKEN", "ACCESS_TOKEN")
+        .env("MONZ0_REFRESH_TOKEN", "REFRESH_TOKEN")
+        .env("MONZ0_CLIENT_ID", "CLIENT_ID")
+        .env("MONZ0_CLIENT_SECRET", "CLIENT_SECRET")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    assert_eq!(balances(&server), vec![10_000, 5_000, 6_000, 0]);
+    assert_eq!(server.refreshes(), 1);
+
+    // the auth file is left alone
+    let auth = fs::read_to_string(dir.path().join("monz0").join("auth.yml")).unwrap();
+    assert!(auth.contains("STALE_ACCESS_TOKEN"));
+}
+
+#[test]
+fn auth_file_overrides_environment() {
+    let server = Server::start(fixture(true));
+    let dir = config_dir(CONFIG, BASIC_AUTH);
+    let auth_file = dir.path().join("headless.yml");
+    fs::write(&auth_file, REFRESHABLE_AUTH).unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_monz0"))
+        .args(["run", "--auth-file", auth_file.to_str().unwrap()])
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("MONZ0_API_URL", server.url())
+        .env("MONZ0_ACCESS_TOKEN", "WRONG_ACCESS_TOKEN")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    assert_eq!(balances(&server), vec![10_000, 5_000, 6_000, 0]);
+
+    // the rotated tokens are saved to the auth file
+    let auth = fs::read_to_string(&auth_file).unwrap();
+    assert!(auth.contains("REFRESH_TOKEN_1"));
+}
+
+#[test]
+fn profile() {
+    let server = Server::start(fixture(false));
+    let dir = config_dir("[]", "access_token: WRONG_ACCESS_TOKEN\n");
+
+    let profile_dir = dir.path().join("monz0").join("profiles").join("joint");
+    fs::create_dir_all(&profile_dir).unwrap();
+    fs::write(profile_dir.join("config.yml"), CONFIG).unwrap();
+    fs::write(profile_dir.join("auth.yml"), BASIC_AUTH).unwrap();
+
+    let output = monz0(dir.path(), &server, &["run", "--profile", "joint"]);
+    assert!(output.status.success());
+
+    assert_eq!(balances(&server), vec![10_000, 5_000, 6_000, 0]);
+}
+
+#[test]
+fn show_loaded_files() {
+    let server = Server::start(fixture(false));
+    let dir = config_dir("[]", BASIC_AUTH);
+    let config = dir.path().join("joint.yml");
+    let auth = dir.path().join("joint-auth.yml");
+    fs::write(&config, CONFIG).unwrap();
+
+    let output = monz0(
+        dir.path(),
+        &server,
+        &[
+            "--config",
+            config.to_str().unwrap(),
+            "--auth",
+            auth.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(&format!("config: {}", config.display())),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains(&format!("credentials: {}", auth.display())),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("acc_1234"), "{}", stdout);
+}
+
+#[test]
+fn missing_config_file() {
+    let server = Server::start(fixture(false));
+    let dir = config_dir(CONFIG, BASIC_AUTH);
+    let config = dir.path().join("missing.yml");
+
+    let output = monz0(
+        dir.path(),
+        &server,
+        &["run", "--config", config.to_str().unwrap()],
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("doesn't exist"));
+    assert!(!config.exists());
+
+    assert_eq!(balances(&server), vec![20_000, 0, 1_000, 0]);
+}
+
+#[test]
+fn validate_reports_error_location() {
+    const TYPO: &str = r#"
+version: 2
+operations:
+  - sweep:
+      account_id: acc_1234
+      acount_goal: 100
+      pots:
+        - bills
+"#;
+
+    let server = Server::start(fixture(false));
+    let dir = config_dir(TYPO, BASIC_AUTH);
+
+    let output = monz0(dir.path(), &server, &["validate"]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("config.yml:6:7: "), "{}", stdout);
+    assert!(
+        stdout.contains("operations[0].sweep: unknown field `acount_goal`"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn validate_migrated_config() {
+    const TYPO: &str = r#"
+- sweep:
+    account_id: acc_1234
+    acount_goal: 100
+    pots:
+      - bills
+"#;
+
+    let server = Server::start(fixture(false));
+    let dir = config_dir(TYPO, BASIC_AUTH);
+
+    let output = monz0(dir.path(), &server, &["validate"]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(
+            "config.yml: the config was migrated from version 0 of the format, so the line and \
+             column of this error aren't known"
+        ),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("unknown field `acount_goal`"), "{}", stdout);
+}
+
+#[test]
+fn validate_live() {
+    const PROBLEMS: &str = r#"
+- sweep:
+    account_id: acc_1234
+    account_goal: 100
+    pots:
+      - bills
+      - old
+- ratio:
+    account_id: acc_1234
+    pots:
+      bills: 1
+      holiday: 1
+"#;
+
+    let server = Server::start(fixture(false));
+    let dir = config_dir(PROBLEMS, BASIC_AUTH);
+
+    let output = monz0(dir.path(), &server, &["validate"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("operation 2 (Ratio): pot 'bills' is also used by operation 1"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("holiday"), "{}", stdout);
+
+    let output = monz0(dir.path(), &server, &["validate", "--live"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("operation 1 (Sweep): pot 'old' has been deleted"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("operation 2 (Ratio): pot 'holiday' not found"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("found 3 problem(s)"), "{}", stdout);
+
+    // nothing is transferred
+    assert_eq!(balances(&server), vec![20_000, 0, 1_000, 0]);
+}
+
+#[test]
+fn config_migrate() {
+    const V0: &str = include_str!("fixtures/config/v0.yml");
+
+    let server = Server::start(fixture(false));
+    let dir = config_dir(V0, BASIC_AUTH);
+    let config = dir.path().join("monz0/config.yml");
+
+    let output = monz0(dir.path(), &server, &["config", "migrate"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("migrated from version 0 to 2"),
+        "{}",
+        stdout
+    );
+
+    let backup = dir.path().join("monz0/config.yml.v0.bak");
+    assert_eq!(fs::read_to_string(backup).unwrap(), V0);
+
+    let migrated = fs::read_to_string(&config).unwrap();
+    assert!(migrated.contains("version: 2"), "{}", migrated);
+    assert!(!migrated.contains("current_account_goal"), "{}", migrated);
+
+    let output = monz0(dir.path(), &server, &["validate"]);
+    assert!(output.status.success());
+
+    let output = monz0(dir.path(), &server, &["config", "migrate"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("already at version 2"), "{}", stdout);
+    assert_eq!(fs::read_to_string(&config).unwrap(), migrated);
+}
+
+#[test]
+fn config_schema() {
+    let server = Server::start(fixture(false));
+    let dir = config_dir(CONFIG, BASIC_AUTH);
+
+    let output = monz0(dir.path(), &server, &["config", "schema"]);
+    assert!(output.status.success());
+
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(schema["properties"]["version"]["const"], 2);
+    for operation in ["Sweep", "Ratio", "TopUp"] {
+        assert!(
+            schema["definitions"][operation].is_object(),
+            "{}",
+            operation
+        );
+    }
+}
+
+#[test]
+fn login() {
+    let mut fixture = fixture(false);
+    fixture.auth.authorisation_code = Some("AUTH_CODE".to_string());
+    let server = Server::start(fixture);
+    let dir = config_dir(CONFIG, BASIC_AUTH);
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_monz0"))
+        .args([
+            "login",
+            "--client-id",
+            "CLIENT_ID",
+            "--client-secret",
+            "CLIENT_SECRET",
+            "--port",
+            "0",
+        ])
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("MONZ0_API_URL", server.url())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // find the authorisation URL in the output
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let authorisation_url = loop {
+        let mut line = String::new();
+        assert_ne!(
+            stdout.read_line(&mut line).unwrap(),
+            0,
+            "no authorisation URL"
+        );
+        if line.starts_with("https://auth.monzo.com/") {
+            break url::Url::parse(line.trim()).unwrap();
+        }
+    };
+
+    let param = |name| {
+        authorisation_url
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .unwrap()
+            .1
+            .into_owned()
+    };
+    let redirect_uri = url::Url::parse(&param("redirect_uri")).unwrap();
+    let state = param("state");
+
+    // act as the browser, following the redirect from Monzo
+    let mut stream = TcpStream::connect((
+        redirect_uri.host_str().unwrap(),
+        redirect_uri.port().unwrap(),
+    ))
+    .unwrap();
+    write!(
+        stream,
+        "GET /callback?code=AUTH_CODE&state={} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        state
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+
+    assert!(child.wait().unwrap().success());
+
+    let auth = fs::read_to_string(dir.path().join("monz0").join("auth.yml")).unwrap();
+    assert!(auth.contains("REFRESH_TOKEN"));
+    assert!(auth.contains("CLIENT_SECRET"));
+}